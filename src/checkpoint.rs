@@ -0,0 +1,341 @@
+use crate::{
+    latency::{self, Percentiles},
+    runner::Window,
+};
+use anyhow::Result;
+use hdrhistogram::Histogram;
+use rusqlite::{ffi, Connection, OpenFlags};
+use serde::{Deserialize, Serialize};
+use std::{
+    ffi::{c_char, c_int, c_void},
+    fmt,
+    ops::{Deref, DerefMut},
+    path::PathBuf,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+/// The `sqlite3_wal_checkpoint_v2` mode of a checkpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckpointMode {
+    Passive,
+    Full,
+    Restart,
+    Truncate,
+}
+
+impl CheckpointMode {
+    fn code(self) -> c_int {
+        match self {
+            CheckpointMode::Passive => ffi::SQLITE_CHECKPOINT_PASSIVE,
+            CheckpointMode::Full => ffi::SQLITE_CHECKPOINT_FULL,
+            CheckpointMode::Restart => ffi::SQLITE_CHECKPOINT_RESTART,
+            CheckpointMode::Truncate => ffi::SQLITE_CHECKPOINT_TRUNCATE,
+        }
+    }
+}
+
+impl fmt::Display for CheckpointMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckpointMode::Passive => write!(f, "passive"),
+            CheckpointMode::Full => write!(f, "full"),
+            CheckpointMode::Restart => write!(f, "restart"),
+            CheckpointMode::Truncate => write!(f, "truncate"),
+        }
+    }
+}
+
+impl FromStr for CheckpointMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "passive" => Ok(CheckpointMode::Passive),
+            "full" => Ok(CheckpointMode::Full),
+            "restart" => Ok(CheckpointMode::Restart),
+            "truncate" => Ok(CheckpointMode::Truncate),
+            _ => Err(anyhow::anyhow!("unknown checkpoint mode {s:?}")),
+        }
+    }
+}
+
+/// How the WAL is checkpointed while an iteration runs.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Checkpoint {
+    /// The behavior of `PRAGMA wal_autocheckpoint = pages`: the connection whose commit takes the WAL to `pages` frames
+    /// runs a PASSIVE checkpoint. It is installed as a WAL hook so its checkpoints can be measured.
+    Auto { pages: u32 },
+    /// Automatic checkpoints disabled and a connection of its own checkpointing in `mode` every `interval_ms`.
+    Background { mode: CheckpointMode, interval_ms: u64 },
+    /// Automatic checkpoints replaced by a WAL hook in which the connection whose commit takes the WAL to `pages` frames
+    /// checkpoints in `mode`.
+    Hook { mode: CheckpointMode, pages: u32 },
+}
+
+impl Checkpoint {
+    /// The `wal_autocheckpoint` in effect on the worker connections, or `None` when a WAL hook replaces the automatic
+    /// checkpoints.
+    pub fn wal_autocheckpoint(self) -> Option<i64> {
+        match self {
+            Checkpoint::Background { .. } => Some(0),
+            Checkpoint::Auto { .. } | Checkpoint::Hook { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for Checkpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Checkpoint::Auto { pages } => write!(f, "auto:{pages}"),
+            Checkpoint::Background { mode, interval_ms } => write!(f, "background:{mode}:{interval_ms}"),
+            Checkpoint::Hook { mode, pages } => write!(f, "hook:{mode}:{pages}"),
+        }
+    }
+}
+
+impl FromStr for Checkpoint {
+    type Err = anyhow::Error;
+
+    /// Parse `auto[:pages]`, `background:<mode>:<interval_ms>` or `hook:<mode>:<pages>`.
+    fn from_str(s: &str) -> Result<Self> {
+        match s.split(':').collect::<Vec<_>>().as_slice() {
+            ["auto"] => Ok(Checkpoint::Auto { pages: 1000 }),
+            ["auto", pages] => Ok(Checkpoint::Auto { pages: pages.parse()? }),
+            ["background", mode, interval_ms] => match interval_ms.parse()? {
+                0 => Err(anyhow::anyhow!("checkpoint interval must be at least 1 ms in {s:?}")),
+                interval_ms => Ok(Checkpoint::Background {
+                    mode: mode.parse()?,
+                    interval_ms,
+                }),
+            },
+            ["hook", mode, pages] => Ok(Checkpoint::Hook {
+                mode: mode.parse()?,
+                pages: pages.parse()?,
+            }),
+            _ => Err(anyhow::anyhow!("unknown checkpoint strategy {s:?}")),
+        }
+    }
+}
+
+/// Where a transaction started relative to the checkpoints, to tell whether any ran alongside it.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Mark {
+    began: u64,
+    running: bool,
+}
+
+/// The checkpoints of an iteration, counted from every connection and thread running them.
+pub(crate) struct Checkpoints {
+    window: Window,
+    start: Instant,
+    began: AtomicU64,
+    ended: AtomicU64,
+    /// The frames in the WAL and how many of them were checkpointed, as of the last checkpoint.
+    wal: Mutex<(i64, i64)>,
+    measured: Mutex<Measured>,
+}
+
+/// Checkpoints that finished within the measured window.
+struct Measured {
+    incomplete: usize,
+    frames: u64,
+    total: Duration,
+    durations: Histogram<u64>,
+}
+
+impl Checkpoints {
+    pub fn new(window: Window, start: Instant) -> Result<Self> {
+        Ok(Self {
+            window,
+            start,
+            began: AtomicU64::new(0),
+            ended: AtomicU64::new(0),
+            wal: Mutex::new((0, 0)),
+            measured: Mutex::new(Measured {
+                incomplete: 0,
+                frames: 0,
+                total: Duration::ZERO,
+                durations: latency::histogram()?,
+            }),
+        })
+    }
+
+    fn begin(&self) -> Instant {
+        self.began.fetch_add(1, Ordering::SeqCst);
+        Instant::now()
+    }
+
+    /// Count a checkpoint that began at `started`. It is `incomplete` when it could not checkpoint the whole WAL. `wal`
+    /// holds the frames it left in the WAL and how many of them were checkpointed, unless it could not run.
+    fn end(&self, started: Instant, incomplete: bool, wal: Option<(i64, i64)>) {
+        let duration = started.elapsed();
+        self.ended.fetch_add(1, Ordering::SeqCst);
+        let frames = wal.map_or(0, |(log, checkpointed)| self.frames(log, checkpointed));
+        if self.window.is_measured(self.start, Instant::now()) {
+            let mut measured = self.measured.lock().expect("should not be poisoned");
+            measured.incomplete += incomplete as usize;
+            measured.frames += frames;
+            measured.total += duration;
+            measured.durations.saturating_record(duration.as_micros() as u64);
+        }
+    }
+
+    /// The frames a checkpoint copied into the database. SQLite counts the checkpointed frames from the start of the WAL,
+    /// so only those beyond the last checkpoint's count are new unless a writer has restarted the WAL since.
+    fn frames(&self, log: i64, checkpointed: i64) -> u64 {
+        let mut wal = self.wal.lock().expect("should not be poisoned");
+        let (previous_log, previous_checkpointed) = *wal;
+        *wal = (log, checkpointed);
+        match log < previous_log {
+            true => checkpointed as u64,
+            false => (checkpointed - previous_checkpointed).max(0) as u64,
+        }
+    }
+
+    pub fn mark(&self) -> Mark {
+        let ended = self.ended.load(Ordering::SeqCst);
+        let began = self.began.load(Ordering::SeqCst);
+        Mark {
+            began,
+            running: began > ended,
+        }
+    }
+
+    /// Whether a checkpoint was running at `mark` or has begun since.
+    pub fn overlapped(&self, mark: Mark) -> bool {
+        mark.running || self.began.load(Ordering::SeqCst) > mark.began
+    }
+
+    pub fn metrics(&self) -> CheckpointMetrics {
+        let measured = self.measured.lock().expect("should not be poisoned");
+        CheckpointMetrics {
+            checkpoints: measured.durations.len(),
+            incomplete: measured.incomplete,
+            frames: measured.frames,
+            total_duration_ms: measured.total.as_millis() as u64,
+            duration_us: Percentiles::from(&measured.durations),
+        }
+    }
+}
+
+/// The checkpoints that finished within the measured window.
+#[derive(Debug, Serialize)]
+pub struct CheckpointMetrics {
+    pub checkpoints: u64,
+    /// Checkpoints that could not copy the whole WAL into the database, because of concurrent readers or writers.
+    pub incomplete: usize,
+    /// Frames copied from the WAL into the database.
+    pub frames: u64,
+    pub total_duration_ms: u64,
+    pub duration_us: Percentiles,
+}
+
+/// The context of the WAL hook of a connection.
+struct Hook {
+    mode: CheckpointMode,
+    pages: u32,
+    checkpoints: Arc<Checkpoints>,
+}
+
+extern "C" fn wal_hook(context: *mut c_void, db: *mut ffi::sqlite3, name: *const c_char, frames: c_int) -> c_int {
+    // SAFETY: `context` points to the `Hook` owned by the `Checkpointed` connection that is committing
+    let hook = unsafe { &*(context as *const Hook) };
+    if frames >= 0 && frames as u32 >= hook.pages {
+        let started = hook.checkpoints.begin();
+        let (mut log, mut checkpointed) = (0, 0);
+        // SAFETY: SQLite allows checkpointing the committing database from its WAL hook
+        let rc = unsafe { ffi::sqlite3_wal_checkpoint_v2(db, name, hook.mode.code(), &mut log, &mut checkpointed) };
+        let wal = (log >= 0 && checkpointed >= 0).then_some((log as i64, checkpointed as i64));
+        hook.checkpoints.end(started, rc != ffi::SQLITE_OK || checkpointed < log, wal);
+    }
+    // errors are counted as incomplete checkpoints rather than failing the commit
+    ffi::SQLITE_OK
+}
+
+/// A worker connection with the WAL hook of its [`Checkpoint`] strategy, if any.
+pub(crate) struct Checkpointed {
+    // declared before the hook so the connection is closed before the hook it points to is freed
+    conn: Connection,
+    _hook: Option<Box<Hook>>,
+}
+
+impl Checkpointed {
+    pub fn new(conn: Connection, checkpoint: Option<Checkpoint>, checkpoints: &Arc<Checkpoints>) -> Result<Self> {
+        let (mode, pages) = match checkpoint {
+            None => return Ok(Self { conn, _hook: None }),
+            Some(Checkpoint::Background { .. }) => {
+                conn.prepare("PRAGMA wal_autocheckpoint = 0;")?.query([])?.next()?;
+                return Ok(Self { conn, _hook: None });
+            }
+            Some(Checkpoint::Auto { pages }) => (CheckpointMode::Passive, pages),
+            Some(Checkpoint::Hook { mode, pages }) => (mode, pages),
+        };
+
+        let hook = Box::new(Hook {
+            mode,
+            pages,
+            checkpoints: checkpoints.clone(),
+        });
+        // SAFETY: the hook is freed only after the connection is closed, and replacing the WAL hook also replaces the
+        // automatic checkpoints
+        unsafe {
+            ffi::sqlite3_wal_hook(conn.handle(), Some(wal_hook), &*hook as *const Hook as *mut c_void);
+        }
+
+        Ok(Self { conn, _hook: Some(hook) })
+    }
+}
+
+impl Deref for Checkpointed {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        &self.conn
+    }
+}
+
+impl DerefMut for Checkpointed {
+    fn deref_mut(&mut self) -> &mut Connection {
+        &mut self.conn
+    }
+}
+
+/// Checkpoint the database at `path` in `mode` every `interval` from a connection of its own until `finish_time`.
+pub(crate) fn spawn(
+    path: PathBuf,
+    mode: CheckpointMode,
+    interval: Duration,
+    checkpoints: Arc<Checkpoints>,
+    finish_time: Instant,
+) -> JoinHandle<Result<()>> {
+    std::thread::spawn(move || {
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_WRITE)?;
+        conn.busy_timeout(Duration::from_millis(5000))?;
+        let sql = format!("PRAGMA wal_checkpoint({mode});");
+
+        let mut next = Instant::now() + interval;
+        while next <= finish_time {
+            std::thread::sleep(next.saturating_duration_since(Instant::now()));
+
+            let started = checkpoints.begin();
+            // count failures such as the database being busy as incomplete checkpoints so they never stop an iteration
+            let (busy, log, checkpointed) = conn
+                .query_row(&sql, [], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?, row.get::<_, i64>(2)?)))
+                .unwrap_or((1, -1, -1));
+            let wal = (log >= 0 && checkpointed >= 0).then_some((log, checkpointed));
+            checkpoints.end(started, busy != 0 || checkpointed < log, wal);
+
+            next += interval;
+        }
+
+        Ok(())
+    })
+}
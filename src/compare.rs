@@ -0,0 +1,178 @@
+use crate::{
+    output::{self, Format},
+    runner::Configuration,
+};
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::path::Path;
+
+/// Join two result files on their configuration and report the throughput and retry changes as Markdown. Throughput
+/// changes larger than `threshold` percent are flagged as a regression or improvement.
+pub fn compare(baseline: &Path, candidate: &Path, threshold: f64) -> Result<String> {
+    report(&read(baseline)?, &read(candidate)?, threshold)
+}
+
+/// Compare the results of two runs, each paired with its configuration.
+fn report(baseline: &[(Configuration, Value)], candidate: &[(Configuration, Value)], threshold: f64) -> Result<String> {
+    // label each configuration by the fields that differ between them
+    let flattened = baseline
+        .iter()
+        .chain(candidate)
+        .map(|(configuration, _)| {
+            let mut fields = Vec::new();
+            output::flatten("", &serde_json::to_value(configuration)?, &mut fields);
+            Ok(fields)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let label = |configuration: &Configuration| -> Result<String> {
+        let mut fields = Vec::new();
+        output::flatten("", &serde_json::to_value(configuration)?, &mut fields);
+        let label = fields
+            .into_iter()
+            .filter(|(key, value)| {
+                flattened
+                    .iter()
+                    .any(|other| other.iter().any(|(other_key, other_value)| key == other_key && value != other_value))
+            })
+            .map(|(key, value)| match value {
+                Value::String(value) => format!("{key}={value}"),
+                value => format!("{key}={value}"),
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        Ok(if label.is_empty() { "all".to_string() } else { label })
+    };
+
+    let mut rows = Vec::new();
+    let mut only_in_baseline = Vec::new();
+    for (configuration, before) in baseline {
+        let Some((_, after)) = candidate.iter().find(|(other, _)| other == configuration) else {
+            only_in_baseline.push(label(configuration)?);
+            continue;
+        };
+
+        let (tps_delta, tps_change) = change(before, after, "tps");
+        let (retries_delta, retries_change) = change(before, after, "retries");
+        let status = match tps_change {
+            Some(tps_change) if tps_change < -threshold => "regression",
+            Some(tps_change) if tps_change > threshold => "improvement",
+            _ => "",
+        };
+
+        rows.push(json!({
+            "configuration": label(configuration)?,
+            "baseline_tps": before["tps"],
+            "candidate_tps": after["tps"],
+            "tps_delta": tps_delta,
+            "tps_change_pct": tps_change,
+            "baseline_retries": before["retries"],
+            "candidate_retries": after["retries"],
+            "retries_delta": retries_delta,
+            "retries_change_pct": retries_change,
+            "status": status,
+        }));
+    }
+    let only_in_candidate = candidate
+        .iter()
+        .filter(|(configuration, _)| !baseline.iter().any(|(other, _)| other == configuration))
+        .map(|(configuration, _)| label(configuration))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut report = output::render(Format::Markdown, &rows)?;
+    for (heading, labels) in [("Only in baseline", only_in_baseline), ("Only in candidate", only_in_candidate)] {
+        if !labels.is_empty() {
+            report.push_str(&format!("\n{heading}:\n\n"));
+            for label in labels {
+                report.push_str(&format!("- {label}\n"));
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// Read a result file in either the `json` or `jsonl` format.
+fn read(path: &Path) -> Result<Vec<(Configuration, Value)>> {
+    configured(output::read(path, Format::Json).or_else(|_| output::read(path, Format::Jsonl))?)
+}
+
+/// Pair each result with its configuration.
+fn configured(results: Vec<Value>) -> Result<Vec<(Configuration, Value)>> {
+    results
+        .into_iter()
+        .map(|result| Ok((serde_json::from_value(result.clone())?, result)))
+        .collect()
+}
+
+/// The absolute change of a numeric field and the relative change in percent, rounded to one decimal place.
+fn change(before: &Value, after: &Value, field: &str) -> (Option<f64>, Option<f64>) {
+    match (before[field].as_f64(), after[field].as_f64()) {
+        (Some(before), Some(after)) => {
+            let relative = (before != 0.0).then(|| ((after - before) / before * 1000.0).round() / 10.0);
+            (Some(after - before), relative)
+        }
+        _ => (None, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(n_threads: usize, tps: u64, retries: u64) -> Value {
+        json!({
+            "behavior": "DEFERRED",
+            "seed": 1000,
+            "duration_secs": 10,
+            "workload": "builtin",
+            "n_threads": n_threads,
+            "operations": { "scan": 10, "update": 1 },
+            "distribution": { "type": "uniform" },
+            "pragmas": {},
+            "retry_policy": { "backoff": { "type": "immediate" }, "max_attempts": null, "deadline_ms": null },
+            "tps": tps,
+            "retries": retries,
+        })
+    }
+
+    fn line<'a>(report: &'a str, label: &str) -> &'a str {
+        report
+            .lines()
+            .find(|line| line.contains(&format!("| {label} |")))
+            .unwrap_or_else(|| panic!("no row for {label} in\n{report}"))
+    }
+
+    #[test]
+    fn flags_changes_beyond_the_threshold() {
+        let baseline = configured(vec![result(1, 1000, 10), result(2, 1000, 0), result(4, 1000, 10), result(8, 1000, 10)]).unwrap();
+        let candidate = configured(vec![result(1, 850, 20), result(2, 1200, 0), result(4, 1049, 10), result(16, 1000, 10)]).unwrap();
+        let report = report(&baseline, &candidate, 5.0).unwrap();
+
+        let regression = line(&report, "n_threads=1");
+        assert!(regression.contains("| regression |"), "{regression}");
+        assert!(regression.contains("| -15.0 |"), "{regression}");
+        assert!(regression.contains("| 100.0 |"), "{regression}");
+
+        // the change from zero retries has no percentage
+        let improvement = line(&report, "n_threads=2");
+        assert!(improvement.contains("| improvement |"), "{improvement}");
+        assert!(improvement.contains("| 20.0 |"), "{improvement}");
+        assert!(improvement.contains("|  |"), "{improvement}");
+
+        // 4.9% is within the threshold
+        let unchanged = line(&report, "n_threads=4");
+        assert!(unchanged.contains("| 4.9 |"), "{unchanged}");
+        assert!(!unchanged.contains("regression") && !unchanged.contains("improvement"), "{unchanged}");
+
+        assert!(report.contains("Only in baseline:\n\n- n_threads=8\n"), "{report}");
+        assert!(report.contains("Only in candidate:\n\n- n_threads=16\n"), "{report}");
+    }
+
+    #[test]
+    fn labels_identical_configurations_as_all() {
+        let results = configured(vec![result(1, 1000, 0)]).unwrap();
+        let report = report(&results, &results, 5.0).unwrap();
+        assert!(line(&report, "all").contains("| 0.0 |"), "{report}");
+        assert!(!report.contains("Only in"), "{report}");
+    }
+}
@@ -0,0 +1,319 @@
+use anyhow::Result;
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+/// How keys are chosen from the seeded key space.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum KeyDistribution {
+    /// Every key is equally likely.
+    Uniform,
+    /// Zipfian with the lowest keys being the most popular. `skew` must be between 0 and 1 exclusive.
+    Zipfian { skew: f64 },
+    /// `operations` fraction of operations target the lowest `keys` fraction of keys.
+    Hotspot { operations: f64, keys: f64 },
+    /// Zipfian with the most recently inserted (highest) keys being the most popular.
+    Latest { skew: f64 },
+    /// Each thread walks the key space in order from its own starting offset.
+    Sequential,
+}
+
+impl fmt::Display for KeyDistribution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyDistribution::Uniform => write!(f, "uniform"),
+            KeyDistribution::Zipfian { skew } => write!(f, "zipfian:{skew}"),
+            KeyDistribution::Hotspot { operations, keys } => write!(f, "hotspot:{operations}:{keys}"),
+            KeyDistribution::Latest { skew } => write!(f, "latest:{skew}"),
+            KeyDistribution::Sequential => write!(f, "sequential"),
+        }
+    }
+}
+
+impl FromStr for KeyDistribution {
+    type Err = anyhow::Error;
+
+    /// Parse `uniform`, `zipfian[:skew]`, `hotspot[:operations:keys]`, `latest[:skew]` or `sequential`.
+    fn from_str(s: &str) -> Result<Self> {
+        let fraction = |value: &str| -> Result<f64> {
+            let value = value.parse::<f64>()?;
+            if value <= 0.0 || value >= 1.0 {
+                return Err(anyhow::anyhow!("expected a value between 0 and 1 exclusive, got {value}"));
+            }
+            Ok(value)
+        };
+
+        match s.split(':').collect::<Vec<_>>().as_slice() {
+            ["uniform"] => Ok(KeyDistribution::Uniform),
+            ["zipfian"] => Ok(KeyDistribution::Zipfian { skew: 0.99 }),
+            ["zipfian", skew] => Ok(KeyDistribution::Zipfian { skew: fraction(skew)? }),
+            ["hotspot"] => Ok(KeyDistribution::Hotspot { operations: 0.8, keys: 0.2 }),
+            ["hotspot", operations, keys] => Ok(KeyDistribution::Hotspot {
+                operations: fraction(operations)?,
+                keys: fraction(keys)?,
+            }),
+            ["latest"] => Ok(KeyDistribution::Latest { skew: 0.99 }),
+            ["latest", skew] => Ok(KeyDistribution::Latest { skew: fraction(skew)? }),
+            ["sequential"] => Ok(KeyDistribution::Sequential),
+            _ => Err(anyhow::anyhow!("unknown key distribution {s:?}")),
+        }
+    }
+}
+
+/// Samples existing keys according to a [`KeyDistribution`] and allocates new ones. Clones share the allocated keys so
+/// threads never allocate the same key.
+#[derive(Debug, Clone)]
+pub struct KeySampler {
+    distribution: KeyDistribution,
+    /// The keys `0..existing` that existed when the sampler was created.
+    existing: u64,
+    /// The number of keys allocated since, which follow the existing ones at `offset` and every `stride`th key after.
+    allocated: Arc<AtomicU64>,
+    offset: u64,
+    stride: u64,
+    zipfian: Option<Zipfian>,
+    cursor: u64,
+}
+
+impl KeySampler {
+    /// Sample from keys `0..keys`. Keys are allocated from `keys` upwards.
+    pub fn new(distribution: KeyDistribution, keys: u64) -> Result<Self> {
+        if keys == 0 {
+            return Err(anyhow::anyhow!("no keys to sample, the table is empty"));
+        }
+        let zipfian = match distribution {
+            KeyDistribution::Zipfian { skew } | KeyDistribution::Latest { skew } if skew <= 0.0 || skew >= 1.0 => {
+                return Err(anyhow::anyhow!("expected a skew between 0 and 1 exclusive, got {skew}"));
+            }
+            KeyDistribution::Zipfian { skew } | KeyDistribution::Latest { skew } => Some(Zipfian::new(keys, skew)),
+            _ => None,
+        };
+
+        Ok(Self {
+            distribution,
+            existing: keys,
+            allocated: Arc::new(AtomicU64::new(0)),
+            offset: 0,
+            stride: 1,
+            zipfian,
+            cursor: 0,
+        })
+    }
+
+    /// Spread the starting position of sequential sampling so threads do not walk the same keys.
+    pub fn for_thread(mut self, thread_id: usize, n_threads: usize) -> Self {
+        self.cursor = self.keys() / n_threads as u64 * thread_id as u64;
+        self
+    }
+
+    /// Allocate keys independently of other clones by taking every `n_threads`th key from `thread_id` onwards, for
+    /// workers that cannot share memory. Only the keys this sampler allocated are sampled besides the existing ones, as
+    /// the keys of other workers may not have been inserted yet.
+    pub fn striped(mut self, thread_id: usize, n_threads: usize) -> Self {
+        self.allocated = Arc::new(AtomicU64::new(0));
+        self.offset = thread_id as u64;
+        self.stride = n_threads as u64;
+        self
+    }
+
+    /// The number of keys that can be sampled, including any allocated since the sampler was created.
+    pub fn keys(&self) -> u64 {
+        self.existing + self.allocated.load(Ordering::Relaxed)
+    }
+
+    /// Allocate a key that has not been sampled or allocated before.
+    pub fn allocate(&self) -> u64 {
+        self.key(self.existing + self.allocated.fetch_add(1, Ordering::Relaxed))
+    }
+
+    pub fn sample<R: Rng + ?Sized>(&mut self, rng: &mut R) -> u64 {
+        let index = self.index(rng);
+        self.key(index)
+    }
+
+    /// The position of a sampled key among the [`KeySampler::keys`], between 0 and 1.
+    pub fn fraction<R: Rng + ?Sized>(&mut self, rng: &mut R) -> f64 {
+        self.index(rng) as f64 / self.keys() as f64
+    }
+
+    /// The key at `index` of the existing keys followed by the allocated ones.
+    fn key(&self, index: u64) -> u64 {
+        match index.checked_sub(self.existing) {
+            Some(allocated) => self.existing + self.offset + allocated * self.stride,
+            None => index,
+        }
+    }
+
+    /// Draw the index of a key in `0..keys()` from the distribution.
+    fn index<R: Rng + ?Sized>(&mut self, rng: &mut R) -> u64 {
+        let keys = self.keys();
+        match self.distribution {
+            KeyDistribution::Uniform => rng.gen_range(0..keys),
+            KeyDistribution::Zipfian { .. } => self.zipfian.as_ref().unwrap().sample(rng),
+            KeyDistribution::Hotspot {
+                operations,
+                keys: hot_fraction,
+            } => {
+                let hot_keys = ((keys as f64 * hot_fraction) as u64).clamp(1, keys);
+                if hot_keys == keys || rng.gen_bool(operations) {
+                    rng.gen_range(0..hot_keys)
+                } else {
+                    rng.gen_range(hot_keys..keys)
+                }
+            }
+            KeyDistribution::Latest { .. } => keys - 1 - self.zipfian.as_ref().unwrap().sample(rng),
+            KeyDistribution::Sequential => {
+                let key = self.cursor % keys;
+                self.cursor = self.cursor.wrapping_add(1);
+                key
+            }
+        }
+    }
+}
+
+/// Zipfian generator from "Quickly Generating Billion-Record Synthetic Databases" (Gray et al.) as used by YCSB.
+#[derive(Debug, Clone)]
+struct Zipfian {
+    items: u64,
+    theta: f64,
+    alpha: f64,
+    zetan: f64,
+    eta: f64,
+}
+
+impl Zipfian {
+    fn new(items: u64, theta: f64) -> Self {
+        let zeta = |n: u64| (1..=n).map(|i| 1.0 / (i as f64).powf(theta)).sum::<f64>();
+        let zeta2 = zeta(2);
+        let zetan = zeta(items);
+
+        Self {
+            items,
+            theta,
+            alpha: 1.0 / (1.0 - theta),
+            zetan,
+            eta: (1.0 - (2.0 / items as f64).powf(1.0 - theta)) / (1.0 - zeta2 / zetan),
+        }
+    }
+
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> u64 {
+        let u = rng.gen::<f64>();
+        let uz = u * self.zetan;
+        if uz < 1.0 {
+            return 0;
+        }
+        if uz < 1.0 + 0.5f64.powf(self.theta) {
+            return 1.min(self.items - 1);
+        }
+        ((self.items as f64 * (self.eta * u - self.eta + 1.0).powf(self.alpha)) as u64).min(self.items - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DISTRIBUTIONS: [&str; 8] = [
+        "uniform",
+        "zipfian",
+        "zipfian:0.5",
+        "hotspot",
+        "hotspot:0.9:0.1",
+        "latest",
+        "latest:0.75",
+        "sequential",
+    ];
+
+    #[test]
+    fn parses_what_it_displays() {
+        for s in DISTRIBUTIONS {
+            let distribution = s.parse::<KeyDistribution>().unwrap();
+            assert_eq!(distribution.to_string().parse::<KeyDistribution>().unwrap(), distribution);
+        }
+        assert_eq!("zipfian".parse::<KeyDistribution>().unwrap().to_string(), "zipfian:0.99");
+        assert_eq!("hotspot".parse::<KeyDistribution>().unwrap().to_string(), "hotspot:0.8:0.2");
+    }
+
+    #[test]
+    fn rejects_invalid_distributions() {
+        for s in [
+            "",
+            "normal",
+            "zipfian:0",
+            "zipfian:1",
+            "latest:1.5",
+            "hotspot:0.8",
+            "hotspot:0.8:abc",
+            "uniform:1",
+        ] {
+            assert!(s.parse::<KeyDistribution>().is_err(), "{s:?}");
+        }
+    }
+
+    #[test]
+    fn samples_stay_within_the_keys() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(0);
+        for s in DISTRIBUTIONS {
+            for keys in [1, 2, 10, 1000] {
+                let mut sampler = KeySampler::new(s.parse().unwrap(), keys).unwrap().for_thread(1, 3);
+                for _ in 0..10_000 {
+                    let key = sampler.sample(&mut rng);
+                    assert!(key < keys, "{s} sampled {key} of {keys} keys");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn striped_samples_stay_within_their_own_keys() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(0);
+        for s in DISTRIBUTIONS {
+            for keys in [1, 10, 1000] {
+                let mut sampler = KeySampler::new(s.parse().unwrap(), keys).unwrap().for_thread(2, 4).striped(2, 4);
+                for _ in 0..10_000 {
+                    let key = sampler.sample(&mut rng);
+                    assert!(key < keys, "{s} sampled {key} of {keys} keys before allocating");
+                }
+
+                let allocated = (0..5).map(|_| sampler.allocate()).collect::<Vec<_>>();
+                assert_eq!(allocated, (0..5).map(|n| keys + 2 + n * 4).collect::<Vec<_>>());
+                let mut sampled_allocated = false;
+                for _ in 0..10_000 {
+                    let key = sampler.sample(&mut rng);
+                    assert!(
+                        key < keys || allocated.contains(&key),
+                        "{s} sampled {key} of {keys} keys and {allocated:?}"
+                    );
+                    sampled_allocated |= allocated.contains(&key);
+                }
+                assert!(sampled_allocated || s.starts_with("zipfian"), "{s} never sampled {allocated:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_an_empty_key_space() {
+        for s in DISTRIBUTIONS {
+            assert!(KeySampler::new(s.parse().unwrap(), 0).is_err(), "{s:?}");
+        }
+    }
+
+    #[test]
+    fn allocates_keys_once() {
+        let sampler = KeySampler::new(KeyDistribution::Uniform, 10).unwrap();
+        let other = sampler.clone();
+        assert_eq!([sampler.allocate(), other.allocate(), sampler.allocate()], [10, 11, 12]);
+        assert_eq!(other.keys(), 13);
+
+        let striped = KeySampler::new(KeyDistribution::Uniform, 10).unwrap().striped(1, 4);
+        assert_eq!([striped.allocate(), striped.allocate()], [11, 15]);
+    }
+}
@@ -0,0 +1,116 @@
+use rusqlite::ffi;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt};
+
+/// Where in a transaction an error happened.
+#[derive(Debug, Clone, Copy)]
+pub enum Phase<'a> {
+    Begin,
+    /// Preparing or stepping the statement of the named operation.
+    Operation(&'a str),
+    Commit,
+}
+
+impl fmt::Display for Phase<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Phase::Begin => write!(f, "begin"),
+            Phase::Operation(name) => write!(f, "{name}"),
+            Phase::Commit => write!(f, "commit"),
+        }
+    }
+}
+
+/// Error counts by phase and then by extended result code.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Errors(pub BTreeMap<String, BTreeMap<String, usize>>);
+
+impl Errors {
+    pub fn record(&mut self, phase: Phase, err: &rusqlite::Error) {
+        let code = match err.sqlite_error() {
+            Some(err) => code_name(err.extended_code),
+            None => "OTHER".to_string(),
+        };
+        *self.0.entry(phase.to_string()).or_default().entry(code).or_default() += 1;
+    }
+
+    /// Merge the counts of another thread into this one.
+    pub fn add(&mut self, other: Errors) {
+        for (phase, codes) in other.0 {
+            let counts = self.0.entry(phase).or_default();
+            for (code, count) in codes {
+                *counts.entry(code).or_default() += count;
+            }
+        }
+    }
+}
+
+macro_rules! code_names {
+    ($code:expr, $($name:ident),* $(,)?) => {
+        match $code {
+            $(ffi::$name => Some(stringify!($name)),)*
+            _ => None,
+        }
+    };
+}
+
+/// The symbolic name of an extended result code such as `SQLITE_BUSY_SNAPSHOT`.
+pub fn code_name(extended_code: i32) -> String {
+    let name = |code: i32| {
+        code_names!(
+            code,
+            SQLITE_ERROR,
+            SQLITE_INTERNAL,
+            SQLITE_PERM,
+            SQLITE_ABORT,
+            SQLITE_ABORT_ROLLBACK,
+            SQLITE_BUSY,
+            SQLITE_BUSY_RECOVERY,
+            SQLITE_BUSY_SNAPSHOT,
+            SQLITE_LOCKED,
+            SQLITE_LOCKED_SHAREDCACHE,
+            SQLITE_NOMEM,
+            SQLITE_READONLY,
+            SQLITE_READONLY_RECOVERY,
+            SQLITE_READONLY_CANTLOCK,
+            SQLITE_READONLY_ROLLBACK,
+            SQLITE_READONLY_DBMOVED,
+            SQLITE_INTERRUPT,
+            SQLITE_IOERR,
+            SQLITE_IOERR_READ,
+            SQLITE_IOERR_SHORT_READ,
+            SQLITE_IOERR_WRITE,
+            SQLITE_IOERR_FSYNC,
+            SQLITE_IOERR_TRUNCATE,
+            SQLITE_IOERR_FSTAT,
+            SQLITE_IOERR_LOCK,
+            SQLITE_IOERR_NOMEM,
+            SQLITE_IOERR_SHMOPEN,
+            SQLITE_IOERR_SHMSIZE,
+            SQLITE_IOERR_SHMLOCK,
+            SQLITE_IOERR_SHMMAP,
+            SQLITE_IOERR_MMAP,
+            SQLITE_CORRUPT,
+            SQLITE_NOTFOUND,
+            SQLITE_FULL,
+            SQLITE_CANTOPEN,
+            SQLITE_PROTOCOL,
+            SQLITE_SCHEMA,
+            SQLITE_TOOBIG,
+            SQLITE_CONSTRAINT,
+            SQLITE_CONSTRAINT_PRIMARYKEY,
+            SQLITE_CONSTRAINT_UNIQUE,
+            SQLITE_CONSTRAINT_NOTNULL,
+            SQLITE_MISMATCH,
+            SQLITE_MISUSE,
+            SQLITE_RANGE,
+            SQLITE_NOTADB,
+        )
+    };
+
+    match (name(extended_code), name(extended_code & 0xff)) {
+        (Some(name), _) => name.to_string(),
+        (None, Some(primary)) => format!("{primary}_{}", extended_code >> 8),
+        (None, None) => format!("SQLITE_{extended_code}"),
+    }
+}
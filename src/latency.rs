@@ -0,0 +1,125 @@
+use anyhow::Result;
+use hdrhistogram::Histogram;
+use serde::Serialize;
+use std::time::Duration;
+
+/// The values recorded in a histogram and their counts.
+pub type Recorded = Vec<(u64, u64)>;
+
+/// The highest latency in microseconds the histograms track. Longer ones are recorded as this value.
+const HIGHEST_LATENCY_US: u64 = 60 * 60 * 1_000_000;
+
+/// A histogram of microseconds with 3 significant figures up to [`HIGHEST_LATENCY_US`].
+pub(crate) fn histogram() -> Result<Histogram<u64>> {
+    Ok(Histogram::new_with_bounds(1, HIGHEST_LATENCY_US, 3)?)
+}
+
+/// Latency histograms collected by a worker thread.
+pub struct Latencies {
+    /// Duration of every individual attempt, including ones that were retried.
+    pub attempts: Histogram<u64>,
+    /// Duration from the first attempt until the transaction committed.
+    pub transactions: Histogram<u64>,
+    /// Duration spent waiting for a pooled connection before each attempt.
+    pub pool_waits: Histogram<u64>,
+    /// Like `transactions`, for the transactions that ran while a checkpoint did.
+    pub during_checkpoints: Histogram<u64>,
+}
+
+impl Latencies {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            attempts: histogram()?,
+            transactions: histogram()?,
+            pool_waits: histogram()?,
+            during_checkpoints: histogram()?,
+        })
+    }
+
+    pub fn record_attempt(&mut self, duration: Duration) {
+        self.attempts.saturating_record(duration.as_micros() as u64);
+    }
+
+    pub fn record_transaction(&mut self, duration: Duration) {
+        self.transactions.saturating_record(duration.as_micros() as u64);
+    }
+
+    pub fn record_pool_wait(&mut self, duration: Duration) {
+        self.pool_waits.saturating_record(duration.as_micros() as u64);
+    }
+
+    pub fn record_during_checkpoint(&mut self, duration: Duration) {
+        self.during_checkpoints.saturating_record(duration.as_micros() as u64);
+    }
+
+    /// The recorded values and their counts of the attempt and transaction histograms, to send between processes.
+    pub fn recorded(&self) -> (Recorded, Recorded) {
+        let recorded = |histogram: &Histogram<u64>| {
+            histogram
+                .iter_recorded()
+                .map(|value| (value.value_iterated_to(), value.count_at_value()))
+                .collect()
+        };
+        (recorded(&self.attempts), recorded(&self.transactions))
+    }
+
+    /// Rebuild the histograms from [`Latencies::recorded`].
+    pub fn from_recorded(attempts: &[(u64, u64)], transactions: &[(u64, u64)]) -> Result<Self> {
+        let mut latencies = Self::new()?;
+        for (value, count) in attempts {
+            latencies.attempts.record_n(*value, *count)?;
+        }
+        for (value, count) in transactions {
+            latencies.transactions.record_n(*value, *count)?;
+        }
+        Ok(latencies)
+    }
+
+    /// Merge the histograms of another thread into this one.
+    pub fn add(&mut self, other: &Latencies) -> Result<()> {
+        self.attempts.add(&other.attempts)?;
+        self.transactions.add(&other.transactions)?;
+        self.pool_waits.add(&other.pool_waits)?;
+        self.during_checkpoints.add(&other.during_checkpoints)?;
+        Ok(())
+    }
+}
+
+/// Latency percentiles in microseconds.
+#[derive(Debug, Serialize)]
+pub struct Percentiles {
+    pub p50: u64,
+    pub p90: u64,
+    pub p99: u64,
+    pub p999: u64,
+    pub max: u64,
+}
+
+impl From<&Histogram<u64>> for Percentiles {
+    fn from(histogram: &Histogram<u64>) -> Self {
+        Self {
+            p50: histogram.value_at_quantile(0.5),
+            p90: histogram.value_at_quantile(0.9),
+            p99: histogram.value_at_quantile(0.99),
+            p999: histogram.value_at_quantile(0.999),
+            max: histogram.max(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_latencies_beyond_milliseconds() {
+        let mut latencies = Latencies::new().unwrap();
+        latencies.record_attempt(Duration::from_micros(150));
+        latencies.record_transaction(Duration::from_millis(10));
+        assert_eq!(latencies.attempts.max(), 150);
+        assert!(latencies.transactions.max() >= 10_000);
+
+        latencies.record_transaction(Duration::from_secs(2 * 60 * 60));
+        assert!(latencies.transactions.max() >= HIGHEST_LATENCY_US);
+    }
+}
@@ -0,0 +1,84 @@
+//! Benchmark SQLite transaction behaviors under concurrent load.
+//!
+//! The `sqlite-bench` binary is a thin wrapper over this crate. To run a single iteration from your own code seed a
+//! database with a [`workload::Workload`] and then [`runner::begin`] an [`runner::Iteration`] against it. Iterations in
+//! [`runner::Mode::Process`] re-execute the current binary as `<binary> worker`, which must call [`process::serve`].
+//! Workloads with `sampled` parameters read their values with [`workload::Workload::load`] before the first iteration.
+//!
+//! ```no_run
+//! use sqlite_bench::{
+//!     distribution::KeyDistribution,
+//!     pragma::Pragmas,
+//!     retry::{Backoff, RetryPolicy},
+//!     runner::{begin, seed, Iteration, Mode, Readers, Window},
+//!     workload::{BuiltinCounts, Workload},
+//!     TransactionBehavior,
+//! };
+//! use std::{path::Path, sync::Arc, time::Duration};
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! let path = Path::new("bench.sqlite");
+//! let workload = Arc::new(Workload::builtin(BuiltinCounts {
+//!     scans: vec![10],
+//!     lookups: vec![0],
+//!     updates: vec![1],
+//!     inserts: vec![0],
+//!     deletes: vec![0],
+//!     reader_scans: vec![0],
+//!     reader_lookups: vec![1],
+//! }));
+//! seed(path, 100_000, &workload)?;
+//!
+//! let result = begin(
+//!     path,
+//!     Some(100_000),
+//!     Window {
+//!         warmup: Duration::from_secs(1),
+//!         duration: Duration::from_secs(10),
+//!         cooldown: Duration::ZERO,
+//!     },
+//!     Duration::from_secs(1),
+//!     &workload,
+//!     Iteration {
+//!         n_threads: 4,
+//!         mix: [("scan".to_string(), 10), ("update".to_string(), 1)].into(),
+//!         distribution: KeyDistribution::Uniform,
+//!         pragmas: Pragmas::default(),
+//!         retry_policy: RetryPolicy {
+//!             backoff: Backoff::Immediate,
+//!             max_attempts: None,
+//!             deadline_ms: None,
+//!         },
+//!         transaction_behavior: TransactionBehavior::Concurrent,
+//!         readers: Some(Readers {
+//!             n_threads: 8,
+//!             mix: [("lookup".to_string(), 1)].into(),
+//!             transaction_behavior: TransactionBehavior::Deferred,
+//!         }),
+//!         mode: Mode::Thread,
+//!         pool_size: None,
+//!         arrival_rate: None,
+//!         checkpoint: None,
+//!     },
+//! )?;
+//! println!("{} tps", result.metrics.tps);
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod checkpoint;
+pub mod compare;
+pub mod distribution;
+pub mod errors;
+pub mod latency;
+pub mod output;
+pub mod pragma;
+pub mod process;
+pub mod retry;
+pub mod runner;
+pub mod series;
+pub mod status;
+mod tasks;
+pub mod workload;
+
+pub use rusqlite::TransactionBehavior;
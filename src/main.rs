@@ -1,32 +1,48 @@
 use anyhow::Result;
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use indicatif::{ProgressBar, ProgressStyle};
 use itertools::Itertools;
-use rand::{distributions::Uniform, prelude::*};
-use rusqlite::{Connection, ErrorCode, OpenFlags, TransactionBehavior};
-use serde::Serialize;
+use rusqlite::Connection;
+use sqlite_bench::{
+    checkpoint::Checkpoint,
+    compare,
+    distribution::KeyDistribution,
+    output::{self, Format},
+    pragma::{self, Pragmas},
+    process,
+    retry::{Backoff, RetryPolicy},
+    runner::{self, begin, seed, Configuration, Iteration, Mode, Readers, Window},
+    workload::{BuiltinCounts, Schema, Workload},
+};
 use std::{
     fs,
-    ops::Add,
     path::{Path, PathBuf},
-    sync::{
-        atomic::{AtomicUsize, Ordering},
-        Arc,
-    },
-    time::{Duration, Instant},
+    sync::Arc,
+    time::Duration,
 };
 
 /// Benchmarking SQLite
 #[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
+#[command(version, about, long_about = None, args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Path to the SQLite file.
-    #[arg(short, long)]
-    path: PathBuf,
+    #[arg(short, long, required = true)]
+    path: Option<PathBuf>,
 
     /// Path to the output result file.
+    #[arg(short, long, required = true)]
+    output: Option<PathBuf>,
+
+    /// Format of the output result file.
+    #[arg(short, long, value_enum, default_value_t = Format::Json)]
+    format: Format,
+
+    /// Keep the results in an existing output file and skip the configurations it already contains.
     #[arg(short, long)]
-    output: PathBuf,
+    resume: bool,
 
     /// Number of records to seed the into the table.
     #[arg(short, long, default_value_t = 1_000_000)]
@@ -36,219 +52,378 @@ struct Args {
     #[arg(short, long, num_args = 1.., value_delimiter = ' ', default_values_t = (1..=16).collect::<Vec<_>>())]
     threads: Vec<usize>,
 
+    /// Path to an existing database to benchmark a copy of instead of seeding one. Requires `--workload`.
+    #[arg(long, requires = "workload")]
+    database: Option<PathBuf>,
+
+    /// Path to a workload definition file to run instead of the builtin workload.
+    #[arg(long, conflicts_with_all = ["scans", "lookups", "updates", "inserts", "deletes", "reader_scans", "reader_lookups", "schemas"])]
+    workload: Option<PathBuf>,
+
+    /// Key distributions to choose row ids and scan prefixes with: uniform, zipfian[:skew], hotspot[:operations:keys], latest[:skew] or sequential.
+    #[arg(short = 'k', long, num_args = 1.., value_delimiter = ' ', default_values_t = vec![KeyDistribution::Uniform])]
+    distributions: Vec<KeyDistribution>,
+
     /// Scan operations to perform per transaction.
     #[arg(short, long, num_args = 1.., value_delimiter = ' ', default_values_t = vec![0, 10])]
     scans: Vec<usize>,
 
+    /// Primary key lookups to perform per transaction.
+    #[arg(long, num_args = 1.., value_delimiter = ' ', default_values_t = vec![0])]
+    lookups: Vec<usize>,
+
     /// Update operations to perform per transaction.
     #[arg(short, long, num_args = 1.., value_delimiter = ' ', default_values_t = vec![0, 1, 10])]
     updates: Vec<usize>,
-}
 
-struct Hexadecimal;
-impl Distribution<char> for Hexadecimal {
-    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> char {
-        *b"0123456789ABCDEF".choose(rng).unwrap() as char
-    }
+    /// Insert operations of new rows to perform per transaction.
+    #[arg(long, num_args = 1.., value_delimiter = ' ', default_values_t = vec![0])]
+    inserts: Vec<usize>,
+
+    /// Delete operations to perform per transaction.
+    #[arg(long, num_args = 1.., value_delimiter = ' ', default_values_t = vec![0])]
+    deletes: Vec<usize>,
+
+    /// Table layouts to run the builtin workload against, each seeded separately.
+    #[arg(long, num_args = 1.., value_delimiter = ' ', value_enum, default_values_t = vec![Schema::Rowid])]
+    schemas: Vec<Schema>,
+
+    /// Transaction behaviors of the writer threads.
+    #[arg(short, long, num_args = 1.., value_delimiter = ' ', value_parser = runner::BEHAVIORS, default_values = ["deferred", "immediate", "concurrent"])]
+    behaviors: Vec<String>,
+
+    /// Number of reader threads to spawn alongside the `--threads` writer threads.
+    #[arg(long, num_args = 1.., value_delimiter = ' ', default_values_t = vec![0])]
+    readers: Vec<usize>,
+
+    /// Transaction behaviors of the reader threads.
+    #[arg(long, num_args = 1.., value_delimiter = ' ', value_parser = runner::BEHAVIORS, default_values = ["deferred"])]
+    reader_behaviors: Vec<String>,
+
+    /// Scan operations to perform per transaction on the reader threads.
+    #[arg(long, num_args = 1.., value_delimiter = ' ', default_values_t = vec![0])]
+    reader_scans: Vec<usize>,
+
+    /// Primary key lookups to perform per transaction on the reader threads.
+    #[arg(long, num_args = 1.., value_delimiter = ' ', default_values_t = vec![1])]
+    reader_lookups: Vec<usize>,
+
+    /// Values of `PRAGMA synchronous` to apply to worker connections. Defaults to the SQLite default.
+    #[arg(long, num_args = 1.., value_delimiter = ' ', value_parser = pragma::SYNCHRONOUS)]
+    synchronous: Vec<String>,
+
+    /// Values of `PRAGMA mmap_size` to apply to worker connections. Defaults to the SQLite default.
+    #[arg(long, num_args = 1.., value_delimiter = ' ')]
+    mmap_size: Vec<i64>,
+
+    /// Values of `PRAGMA cache_size` to apply to worker connections. Defaults to the SQLite default.
+    #[arg(long, num_args = 1.., value_delimiter = ' ', allow_negative_numbers = true)]
+    cache_size: Vec<i64>,
+
+    /// Values of `PRAGMA wal_autocheckpoint` to apply to worker connections. Defaults to the SQLite default.
+    #[arg(long, num_args = 1.., value_delimiter = ' ')]
+    wal_autocheckpoint: Vec<i64>,
+
+    /// Values of `PRAGMA temp_store` to apply to worker connections. Defaults to the SQLite default.
+    #[arg(long, num_args = 1.., value_delimiter = ' ', value_parser = pragma::TEMP_STORE)]
+    temp_store: Vec<String>,
+
+    /// WAL checkpoint strategies: auto[:pages], background:<mode>:<interval_ms> or hook:<mode>:<pages> with a mode of
+    /// passive, full, restart or truncate. Checkpoints are left to `--wal-autocheckpoint` and not measured if not set.
+    #[arg(long, num_args = 1.., value_delimiter = ' ', conflicts_with = "wal_autocheckpoint")]
+    checkpoint: Vec<Checkpoint>,
+
+    /// Backoff strategies to retry busy transactions with: immediate, fixed:<delay_ms> or exponential:<base_ms>:<max_ms>.
+    #[arg(long, num_args = 1.., value_delimiter = ' ', default_values_t = vec![Backoff::Immediate])]
+    backoff: Vec<Backoff>,
+
+    /// Maximum number of attempts before a busy transaction is abandoned. Unlimited if not set.
+    #[arg(long)]
+    max_attempts: Option<u32>,
+
+    /// Number of milliseconds after the first attempt before a busy transaction is abandoned. Unlimited if not set.
+    #[arg(long)]
+    retry_deadline: Option<u64>,
+
+    /// Run workers as threads of this process, as separate processes or as tasks sharing a connection pool.
+    #[arg(long, value_enum, default_value_t = Mode::Thread)]
+    mode: Mode,
+
+    /// Numbers of pooled connections per pool of workers with `--mode async`. Defaults to one per task.
+    #[arg(long, num_args = 1.., value_delimiter = ' ')]
+    pool_size: Vec<usize>,
+
+    /// Transactions per second for the writers to start open loop, measuring latency from when each was due. Writers
+    /// run closed loop if not set.
+    #[arg(long, num_args = 1.., value_delimiter = ' ')]
+    arrival_rate: Vec<u64>,
+
+    /// Number of seconds to measure each iteration for.
+    #[arg(short, long, default_value_t = 30, value_parser = clap::value_parser!(u64).range(1..))]
+    duration: u64,
+
+    /// Number of seconds to run each iteration before measuring.
+    #[arg(short, long, default_value_t = 0)]
+    warmup: u64,
+
+    /// Number of seconds to keep running each iteration after measuring.
+    #[arg(short, long, default_value_t = 0)]
+    cooldown: u64,
+
+    /// Number of milliseconds between throughput samples.
+    #[arg(long, default_value_t = 1000, value_parser = clap::value_parser!(u64).range(1..))]
+    sample_interval: u64,
 }
 
-const SCAN: &str = "SELECT * FROM tbl WHERE substr(c, 1, 16)>=? ORDER BY substr(c, 1, 16) LIMIT 10;";
-const UPDATE: &str = "UPDATE tbl SET b=?, c=? WHERE a=?;";
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Compare two result files and flag throughput changes between matching configurations.
+    Compare {
+        /// Path to the baseline result file.
+        baseline: PathBuf,
 
-#[derive(Debug, Serialize)]
-struct Transactions {
-    behavior: String,
-    seed: usize,
-    n_threads: usize,
-    n_scans: usize,
-    n_updates: usize,
-    retries: usize,
-    transactions: usize,
-    tps: u128,
+        /// Path to the candidate result file.
+        candidate: PathBuf,
+
+        /// Relative throughput change in percent beyond which a configuration is flagged.
+        #[arg(short, long, default_value_t = 5.0)]
+        threshold: f64,
+    },
+    /// Run a single worker of a `--mode process` iteration.
+    #[command(hide = true)]
+    Worker,
 }
 
 fn main() -> Result<()> {
-    let args = Args::parse();
+    let mut args = Args::parse();
+
+    match args.command.take() {
+        Some(Command::Compare {
+            baseline,
+            candidate,
+            threshold,
+        }) => {
+            print!("{}", compare::compare(&baseline, &candidate, threshold)?);
+            Ok(())
+        }
+        Some(Command::Worker) => process::serve(),
+        None => run(args),
+    }
+}
+
+fn run(args: Args) -> Result<()> {
+    let path = args.path.expect("required by clap");
+    let output = args.output.expect("required by clap");
 
-    if args.output.exists() {
-        return Err(anyhow::anyhow!("file already exists {:?}", args.output));
+    let mut results = match (output.exists(), args.resume) {
+        (true, true) => output::read(&output, args.format)?,
+        (true, false) => return Err(anyhow::anyhow!("file already exists {:?}", output)),
+        (false, _) => Vec::new(),
+    };
+    let measured = results
+        .iter()
+        .map(|result| serde_json::from_value::<Configuration>(result.clone()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // the file at `--path` is removed so never let it be the database to copy
+    if let Some(database) = &args.database {
+        if fs::canonicalize(database)? == fs::canonicalize(&path).unwrap_or_default() {
+            return Err(anyhow::anyhow!("--database must not be the same file as --path"));
+        }
     }
 
     // remove any state
-    fs::remove_file(&args.path).ok();
-    fs::remove_file(args.path.join("-shm")).ok();
-    fs::remove_file(args.path.join("-wal")).ok();
+    let mut template = path.as_os_str().to_owned();
+    template.push(".template");
+    let template = PathBuf::from(template);
+    runner::remove(&path);
+    runner::remove(&template);
+
+    let mut workloads = match &args.workload {
+        Some(path) => vec![Workload::from_file(path)?],
+        None => {
+            let builtin = Workload::builtin(BuiltinCounts {
+                scans: args.scans,
+                lookups: args.lookups,
+                updates: args.updates,
+                inserts: args.inserts,
+                deletes: args.deletes,
+                reader_scans: args.reader_scans,
+                reader_lookups: args.reader_lookups,
+            });
+            args.schemas.iter().map(|schema| builtin.clone().with_schema(*schema)).collect()
+        }
+    };
 
-    let iterations = args
-        .threads
+    if args.database.is_some() && workloads[0].next_key.is_none() {
+        return Err(anyhow::anyhow!("--database requires a workload with a next_key query"));
+    }
+
+    // the schema variants share their operations so any of them has the reader mixes of all
+    let reader_mixes = workloads[0].reader_mixes();
+    if args.readers.iter().any(|n_threads| *n_threads != 0) && reader_mixes.is_empty() {
+        return Err(anyhow::anyhow!("workload {:?} has no operations for reader threads", workloads[0].name));
+    }
+    let readers = args
+        .readers
         .iter()
-        .cartesian_product(args.scans)
-        .cartesian_product(args.updates)
-        .cartesian_product([
-            TransactionBehavior::Deferred,
-            TransactionBehavior::Immediate,
-            TransactionBehavior::Concurrent,
-        ])
-        .map(|(((n_threads, n_scans), n_updates), trasaction_behavior)| (*n_threads, n_scans, n_updates, trasaction_behavior))
-        .filter(|(_, n_scans, n_updates, _)| !(*n_scans == 0 && *n_updates == 0))
+        .flat_map(|n_threads| match n_threads {
+            0 => vec![None],
+            n_threads => reader_mixes
+                .iter()
+                .cartesian_product(&args.reader_behaviors)
+                .map(|(mix, behavior)| {
+                    Some(Readers {
+                        n_threads: *n_threads,
+                        mix: mix.clone(),
+                        transaction_behavior: runner::parse_behavior(behavior).expect("validated by clap"),
+                    })
+                })
+                .collect(),
+        })
         .collect::<Vec<_>>();
 
-    // seed database
-    seed(&args.path, args.seed)?;
+    if !args.pool_size.is_empty() && args.mode != Mode::Async {
+        return Err(anyhow::anyhow!("--pool-size requires --mode async"));
+    }
+    if args.pool_size.contains(&0) {
+        return Err(anyhow::anyhow!("--pool-size must be at least 1"));
+    }
+    let pool_sizes = match args.pool_size.is_empty() {
+        true => vec![None],
+        false => args.pool_size.iter().copied().map(Some).collect(),
+    };
+    if args.arrival_rate.contains(&0) {
+        return Err(anyhow::anyhow!("--arrival-rate must be at least 1"));
+    }
+    let arrival_rates = match args.arrival_rate.is_empty() {
+        true => vec![None],
+        false => args.arrival_rate.iter().copied().map(Some).collect(),
+    };
 
-    let pb = ProgressBar::new(iterations.len() as u64).with_style(ProgressStyle::with_template("{wide_bar} {pos}/{len} {eta_precise}")?);
-    pb.inc(0);
+    if !args.checkpoint.is_empty() && args.mode == Mode::Process {
+        return Err(anyhow::anyhow!("--checkpoint cannot be used with --mode process"));
+    }
+    let checkpoints = match args.checkpoint.is_empty() {
+        true => vec![None],
+        false => args.checkpoint.iter().copied().map(Some).collect(),
+    };
+
+    let window = Window {
+        warmup: Duration::from_secs(args.warmup),
+        duration: Duration::from_secs(args.duration),
+        cooldown: Duration::from_secs(args.cooldown),
+    };
+
+    // fail early rather than in every iteration when the SQLite build lacks a behavior such as CONCURRENT
+    let behaviors = args
+        .behaviors
+        .iter()
+        .map(|behavior| runner::parse_behavior(behavior))
+        .collect::<Result<Vec<_>>>()?;
+    for behavior in args.behaviors.iter().chain(&args.reader_behaviors).unique() {
+        runner::check_behavior(runner::parse_behavior(behavior)?)?;
+    }
 
-    let mut results = Vec::with_capacity(iterations.len());
+    // record the SQLite defaults for any PRAGMA that is not swept from the database of the first workload
+    // nothing is seeded when benchmarking an existing database so the seed is not part of its configurations
+    let seed = args.database.is_none().then_some(args.seed);
+    prepare(&path, &template, args.database.as_deref(), args.seed, &mut workloads[0])?;
+    let defaults = Pragmas::effective(&Connection::open(&path)?)?;
+
+    let iterations = |workload: &Workload| {
+        args.threads
+            .iter()
+            .cartesian_product(workload.mixes())
+            .cartesian_product(args.distributions.clone())
+            .cartesian_product(Pragmas::sweep(
+                &args.synchronous,
+                &args.mmap_size,
+                &args.cache_size,
+                &args.wal_autocheckpoint,
+                &args.temp_store,
+            ))
+            .cartesian_product(args.backoff.iter().map(|backoff| RetryPolicy {
+                backoff: *backoff,
+                max_attempts: args.max_attempts,
+                deadline_ms: args.retry_deadline,
+            }))
+            .cartesian_product(behaviors.clone())
+            .cartesian_product(readers.clone())
+            .cartesian_product(pool_sizes.clone())
+            .cartesian_product(arrival_rates.clone())
+            .cartesian_product(checkpoints.clone())
+            .map(
+                |(
+                    ((((((((n_threads, mix), distribution), pragmas), retry_policy), transaction_behavior), readers), pool_size), arrival_rate),
+                    checkpoint,
+                )| Iteration {
+                    n_threads: *n_threads,
+                    mix,
+                    distribution,
+                    pragmas: pragmas.or(&defaults),
+                    retry_policy,
+                    transaction_behavior,
+                    readers,
+                    mode: args.mode,
+                    pool_size,
+                    arrival_rate,
+                    checkpoint,
+                },
+            )
+            .filter(|iteration| !measured.contains(&iteration.configuration(seed, window, workload)))
+            .collect::<Vec<_>>()
+    };
+    let pending = workloads
+        .into_iter()
+        .enumerate()
+        .map(|(index, workload)| (index, iterations(&workload), workload))
+        .filter(|(_, iterations, _)| !iterations.is_empty())
+        .collect::<Vec<_>>();
 
-    for (n_threads, n_scans, n_updates, trasaction_behavior) in iterations {
-        results.push(begin(&args.path, args.seed, n_threads, n_scans, n_updates, trasaction_behavior)?);
-        pb.inc(1);
+    let pb = ProgressBar::new(pending.iter().map(|(_, iterations, _)| iterations.len() as u64).sum())
+        .with_style(ProgressStyle::with_template("{wide_bar} {pos}/{len} {eta_precise}")?);
+    pb.inc(0);
+
+    for (index, iterations, mut workload) in pending {
+        // the first workload was prepared to read the defaults
+        if index != 0 {
+            prepare(&path, &template, args.database.as_deref(), args.seed, &mut workload)?;
+        }
+        let workload = Arc::new(workload);
+
+        for iteration in iterations {
+            runner::restore(&template, &path)?;
+            results.push(serde_json::to_value(begin(
+                &path,
+                seed,
+                window,
+                Duration::from_millis(args.sample_interval),
+                &workload,
+                iteration,
+            )?)?);
+            output::write(&output, args.format, &results)?;
+            pb.inc(1);
+        }
     }
 
     pb.finish();
 
-    fs::write(args.output, serde_json::to_string_pretty(&results)?)?;
-
     // remove any state
-    fs::remove_file(&args.path).ok();
-    fs::remove_file(args.path.join("-shm")).ok();
-    fs::remove_file(args.path.join("-wal")).ok();
+    runner::remove(&path);
+    runner::remove(&template);
 
     Ok(())
 }
 
-fn seed(path: &Path, rows: usize) -> Result<()> {
-    let conn = Connection::open(path)?;
-    conn.execute_batch(&format!(
-        "
-        PRAGMA journal_mode = WAL;
-        PRAGMA mmap_size = 1000000000;
-        PRAGMA synchronous = off;
-        PRAGMA journal_size_limit = 16777216;
-
-        CREATE TABLE tbl(
-            a INTEGER PRIMARY KEY,
-            b BLOB(200),
-            c CHAR(64)
-        );
-
-        -- https://www.sqlite.org/series.html
-        WITH RECURSIVE generate_series(value) AS (
-            SELECT 0
-            UNION ALL
-            SELECT value+1 FROM generate_series
-            WHERE value < {rows}
-        )
-        INSERT INTO tbl
-        SELECT value, randomblob(200), hex(randomblob(32))
-        FROM generate_series;
-
-        CREATE INDEX tbl_i1 ON tbl(substr(c, 1, 16));
-        CREATE INDEX tbl_i2 ON tbl(substr(c, 2, 16));
-        "
-    ))?;
-
-    Ok(())
-}
-
-fn begin(
-    path: &Path,
-    seed: usize,
-    n_threads: usize,
-    n_scans: usize,
-    n_updates: usize,
-    trasaction_behavior: TransactionBehavior,
-) -> Result<Transactions> {
-    let transactions = Arc::new(AtomicUsize::new(0));
-    let retries = Arc::new(AtomicUsize::new(0));
-    (0..n_threads)
-        .map(|thread_id| {
-            let path = path.to_path_buf();
-            let transactions = transactions.clone();
-            let retries = retries.clone();
-
-            std::thread::spawn(move || {
-                let between_ids = Uniform::from(0..1_000_000);
-                let mut rng: StdRng = SeedableRng::seed_from_u64(thread_id as u64);
-                let mut conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_WRITE)?;
-                conn.busy_timeout(Duration::from_millis(5000))?;
-
-                let finish_time = Instant::now().add(Duration::from_secs(30));
-                while Instant::now() <= finish_time {
-                    let scans = (0..n_scans)
-                        .map(|_| (&mut rng).sample_iter(&Hexadecimal).take(16).map(char::from).collect::<String>())
-                        .collect::<Vec<_>>();
-                    let updates: Vec<([u8; 200], String, i32)> = (0..n_updates)
-                        .map(|_| {
-                            let mut bytes = [0; 200];
-                            rng.fill_bytes(&mut bytes);
-                            (
-                                bytes,
-                                (&mut rng).sample_iter(&Hexadecimal).take(64).map(char::from).collect::<String>(),
-                                between_ids.sample(&mut rng),
-                            )
-                        })
-                        .collect::<Vec<_>>();
-
-                    loop {
-                        let mut transaction = || {
-                            let txn = conn.transaction_with_behavior(trasaction_behavior)?;
-
-                            if !scans.is_empty() {
-                                let mut scan = txn.prepare_cached(SCAN)?;
-                                for random_hex in &scans {
-                                    // Consume the results
-                                    scan.query_map([random_hex], |row| row.get::<_, i32>(0))?.for_each(drop);
-                                }
-                            }
-
-                            if !updates.is_empty() {
-                                let mut update = txn.prepare_cached(UPDATE)?;
-                                for (random_bytes, random_hex, random_id) in &updates {
-                                    update.execute((random_bytes, random_hex, random_id))?;
-                                }
-                            }
-
-                            txn.commit()
-                        };
-
-                        match transaction() {
-                            Err(rusqlite::Error::SqliteFailure(err, _)) if err.code == ErrorCode::DatabaseBusy => {
-                                retries.fetch_add(1, Ordering::Relaxed);
-                                continue;
-                            }
-                            Ok(_) => {
-                                transactions.fetch_add(1, Ordering::Relaxed);
-                                break;
-                            }
-                            err => unimplemented!("{err:?}"),
-                        }
-                    }
-                }
-
-                anyhow::Ok(())
-            })
-        })
-        .collect::<Vec<_>>()
-        .into_iter()
-        .for_each(|thread| thread.join().expect("should not fail").expect("should not fail"));
-
-    Ok(Transactions {
-        behavior: match trasaction_behavior {
-            TransactionBehavior::Deferred => "DEFERRED",
-            TransactionBehavior::Immediate => "IMMEDIATE",
-            TransactionBehavior::Exclusive => "EXCLUSIVE",
-            TransactionBehavior::Concurrent => "CONCURRENT",
-            _ => unreachable!(),
-        }
-        .to_string(),
-        seed,
-        n_threads,
-        n_scans,
-        n_updates,
-        retries: retries.load(Ordering::Relaxed),
-        transactions: transactions.load(Ordering::Relaxed),
-        tps: Duration::from_secs(1).as_nanos() / Duration::from_secs(30).div_f32(transactions.load(Ordering::Relaxed) as f32).as_nanos(),
-    })
+/// Seed or copy the database of `workload` and keep a copy at `template` to restore before every iteration.
+fn prepare(path: &Path, template: &Path, database: Option<&Path>, rows: usize, workload: &mut Workload) -> Result<()> {
+    runner::remove(path);
+    runner::remove(template);
+    match database {
+        Some(database) => runner::import(database, path)?,
+        None => seed(path, rows, workload)?,
+    }
+    runner::save_template(path, template)?;
+    workload.load(&Connection::open(path)?)
 }
@@ -0,0 +1,140 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use serde_json::{Map, Value};
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::Path,
+};
+
+/// The file format results are written in.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Format {
+    /// A pretty printed JSON array.
+    Json,
+    /// One JSON object per line.
+    Jsonl,
+    /// Comma separated values with nested fields flattened into dotted column names.
+    Csv,
+    /// A Markdown table of every field except the time series.
+    Markdown,
+}
+
+/// Read the results of a previous run.
+pub fn read(path: &Path, format: Format) -> Result<Vec<Value>> {
+    let contents = fs::read_to_string(path)?;
+    match format {
+        Format::Json => Ok(serde_json::from_str(&contents)?),
+        Format::Jsonl => Ok(contents
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()?),
+        Format::Csv | Format::Markdown => Err(anyhow::anyhow!("cannot read results in {format:?} format, use json or jsonl")),
+    }
+}
+
+/// Save the results so far once the last of `results` has been added. The `jsonl` format appends the last result. The
+/// other formats replace the output file with all results, written alongside and renamed so an interrupted write never
+/// truncates earlier results.
+pub fn write(path: &Path, format: Format, results: &[Value]) -> Result<()> {
+    if format == Format::Jsonl {
+        if let Some(result) = results.last() {
+            let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+            writeln!(file, "{}", serde_json::to_string(result)?)?;
+        }
+        return Ok(());
+    }
+
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    fs::write(&temporary, render(format, results)?)?;
+    fs::rename(&temporary, path)?;
+
+    Ok(())
+}
+
+pub fn render(format: Format, results: &[Value]) -> Result<String> {
+    Ok(match format {
+        Format::Json => serde_json::to_string_pretty(results)?,
+        Format::Jsonl => results
+            .iter()
+            .map(|result| serde_json::to_string(result).map(|line| line + "\n"))
+            .collect::<Result<_, _>>()?,
+        Format::Csv => {
+            let (columns, rows) = table(results, true);
+            let mut writer = csv::Writer::from_writer(Vec::new());
+            writer.write_record(&columns)?;
+            for row in rows {
+                writer.write_record(row)?;
+            }
+            String::from_utf8(writer.into_inner()?)?
+        }
+        Format::Markdown => {
+            let (columns, rows) = table(results, false);
+            let line = |cells: &[String]| {
+                format!(
+                    "| {} |\n",
+                    cells.iter().map(|cell| cell.replace('|', "\\|")).collect::<Vec<_>>().join(" | ")
+                )
+            };
+            let mut contents = line(&columns);
+            contents.push_str(&line(&vec!["---".to_string(); columns.len()]));
+            for row in rows {
+                contents.push_str(&line(&row));
+            }
+            contents
+        }
+    })
+}
+
+/// Flatten the results into columns named by their dotted path and one row of cells per result. Arrays are kept as
+/// JSON text when `arrays` is set and dropped otherwise.
+fn table(results: &[Value], arrays: bool) -> (Vec<String>, Vec<Vec<String>>) {
+    let flattened = results
+        .iter()
+        .map(|result| {
+            let mut fields = Vec::new();
+            flatten("", result, &mut fields);
+            fields.retain(|(_, value)| arrays || !value.is_array());
+            fields
+        })
+        .collect::<Vec<_>>();
+
+    let mut columns = Vec::<String>::new();
+    for (column, _) in flattened.iter().flatten() {
+        if !columns.contains(column) {
+            columns.push(column.clone());
+        }
+    }
+
+    let rows = flattened
+        .iter()
+        .map(|fields| {
+            let fields = fields.iter().cloned().collect::<Map<_, _>>();
+            columns
+                .iter()
+                .map(|column| match fields.get(column) {
+                    None | Some(Value::Null) => String::new(),
+                    Some(Value::String(value)) => value.clone(),
+                    Some(value) => value.to_string(),
+                })
+                .collect()
+        })
+        .collect();
+
+    (columns, rows)
+}
+
+/// Collect the leaves of `value` under their dotted path.
+pub fn flatten(prefix: &str, value: &Value, fields: &mut Vec<(String, Value)>) {
+    match value {
+        Value::Object(object) => {
+            for (key, value) in object {
+                let key = if prefix.is_empty() { key.clone() } else { format!("{prefix}.{key}") };
+                flatten(&key, value, fields);
+            }
+        }
+        value => fields.push((prefix.to_string(), value.clone())),
+    }
+}
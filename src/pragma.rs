@@ -0,0 +1,89 @@
+use anyhow::Result;
+use itertools::iproduct;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+pub const SYNCHRONOUS: [&str; 4] = ["off", "normal", "full", "extra"];
+pub const TEMP_STORE: [&str; 3] = ["default", "file", "memory"];
+
+/// Per-connection PRAGMAs applied to every worker connection. `None` leaves the SQLite default in place.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Pragmas {
+    pub synchronous: Option<String>,
+    pub mmap_size: Option<i64>,
+    pub cache_size: Option<i64>,
+    pub wal_autocheckpoint: Option<i64>,
+    pub temp_store: Option<String>,
+}
+
+impl Pragmas {
+    /// Every combination of the supplied values. An empty list leaves that PRAGMA at its default.
+    pub fn sweep(synchronous: &[String], mmap_size: &[i64], cache_size: &[i64], wal_autocheckpoint: &[i64], temp_store: &[String]) -> Vec<Pragmas> {
+        fn axis<T: Clone>(values: &[T]) -> Vec<Option<T>> {
+            if values.is_empty() {
+                vec![None]
+            } else {
+                values.iter().cloned().map(Some).collect()
+            }
+        }
+
+        iproduct!(
+            axis(synchronous),
+            axis(mmap_size),
+            axis(cache_size),
+            axis(wal_autocheckpoint),
+            axis(temp_store)
+        )
+        .map(|(synchronous, mmap_size, cache_size, wal_autocheckpoint, temp_store)| Pragmas {
+            synchronous,
+            mmap_size,
+            cache_size,
+            wal_autocheckpoint,
+            temp_store,
+        })
+        .collect()
+    }
+
+    pub fn apply(&self, conn: &Connection) -> Result<()> {
+        let pragmas = [
+            ("synchronous", self.synchronous.clone()),
+            ("mmap_size", self.mmap_size.map(|value| value.to_string())),
+            ("cache_size", self.cache_size.map(|value| value.to_string())),
+            ("wal_autocheckpoint", self.wal_autocheckpoint.map(|value| value.to_string())),
+            ("temp_store", self.temp_store.clone()),
+        ];
+
+        for (name, value) in pragmas {
+            if let Some(value) = value {
+                // some PRAGMAs return the new value so step the statement rather than using `execute`
+                conn.prepare(&format!("PRAGMA {name} = {value};"))?.query([])?.next()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Fill in every PRAGMA left at its default with the value from `defaults`.
+    pub fn or(self, defaults: &Pragmas) -> Pragmas {
+        Pragmas {
+            synchronous: self.synchronous.or_else(|| defaults.synchronous.clone()),
+            mmap_size: self.mmap_size.or(defaults.mmap_size),
+            cache_size: self.cache_size.or(defaults.cache_size),
+            wal_autocheckpoint: self.wal_autocheckpoint.or(defaults.wal_autocheckpoint),
+            temp_store: self.temp_store.or_else(|| defaults.temp_store.clone()),
+        }
+    }
+
+    /// The values in effect on a connection.
+    pub fn effective(conn: &Connection) -> Result<Pragmas> {
+        let value = |name: &str| conn.pragma_query_value(None, name, |row| row.get::<_, i64>(0));
+
+        Ok(Pragmas {
+            synchronous: SYNCHRONOUS.get(value("synchronous")? as usize).map(|name| name.to_string()),
+            mmap_size: Some(value("mmap_size")?),
+            cache_size: Some(value("cache_size")?),
+            wal_autocheckpoint: Some(value("wal_autocheckpoint")?),
+            temp_store: TEMP_STORE.get(value("temp_store")? as usize).map(|name| name.to_string()),
+        })
+    }
+}
@@ -0,0 +1,229 @@
+use crate::{
+    checkpoint::Checkpoints,
+    distribution::{KeyDistribution, KeySampler},
+    errors::Errors,
+    latency::{Latencies, Recorded},
+    pragma::Pragmas,
+    retry::RetryPolicy,
+    runner::{parse_behavior, Arrivals, Tally, Totals, Window, Worker, WorkerHandle},
+    series::Counters,
+    status::Status,
+    workload::Workload,
+};
+use anyhow::Result;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant, SystemTime},
+};
+
+/// How often a worker process reports its counters to the parent.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Everything a worker process needs to run its share of an iteration.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Job {
+    pub path: PathBuf,
+    pub workload: Workload,
+    pub mix: Vec<usize>,
+    pub behavior: String,
+    pub distribution: KeyDistribution,
+    pub next_key: u64,
+    pub pragmas: Pragmas,
+    pub retry_policy: RetryPolicy,
+    pub window: Window,
+    /// When the parent started the iteration, so every process measures the same window.
+    pub started: SystemTime,
+    pub thread_id: usize,
+    /// The number of workers across all processes.
+    pub n_threads: usize,
+    pub arrivals: Option<Arrivals>,
+}
+
+/// A line written by a worker process to its standard output.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Message {
+    /// Running totals of the [`Counters`] so far.
+    Progress {
+        commits: usize,
+        retries: usize,
+        failures: usize,
+        errors: usize,
+    },
+    /// The final measurements once the window has finished.
+    Report {
+        transactions: usize,
+        retries: usize,
+        failures: usize,
+        attempt_latencies: Recorded,
+        transaction_latencies: Recorded,
+        errors: Errors,
+        status: Status,
+        failure: Option<String>,
+    },
+}
+
+impl Message {
+    fn progress(counters: &Counters) -> Self {
+        Message::Progress {
+            commits: counters.commits.load(Ordering::Relaxed),
+            retries: counters.retries.load(Ordering::Relaxed),
+            failures: counters.failures.load(Ordering::Relaxed),
+            errors: counters.errors.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// A worker process that is killed and reaped if it is dropped before exiting, so it never keeps writing to the
+/// database after its iteration has failed.
+struct WorkerProcess(Child);
+
+impl Drop for WorkerProcess {
+    fn drop(&mut self) {
+        // both fail harmlessly once the process has exited and been waited for
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+/// Start a worker process for `job` by re-executing the current binary with the `worker` subcommand. Its progress is
+/// added to `counters` as it runs and its final measurements to `totals`.
+pub(crate) fn spawn(job: Job, totals: Arc<Totals>, counters: Arc<Counters>) -> WorkerHandle {
+    std::thread::spawn(move || {
+        let mut child = WorkerProcess(
+            Command::new(std::env::current_exe()?)
+                .arg("worker")
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .spawn()?,
+        );
+        serde_json::to_writer(child.0.stdin.take().expect("piped"), &job)?;
+
+        let mut reported = None;
+        let (mut commits, mut retries, mut failures, mut errors) = (0, 0, 0, 0);
+        for line in BufReader::new(child.0.stdout.take().expect("piped")).lines() {
+            match serde_json::from_str(&line?)? {
+                Message::Progress {
+                    commits: total_commits,
+                    retries: total_retries,
+                    failures: total_failures,
+                    errors: total_errors,
+                } => {
+                    let add = |counter: &AtomicUsize, total: usize, last: &mut usize| {
+                        counter.fetch_add(total - *last, Ordering::Relaxed);
+                        *last = total;
+                    };
+                    add(&counters.commits, total_commits, &mut commits);
+                    add(&counters.retries, total_retries, &mut retries);
+                    add(&counters.failures, total_failures, &mut failures);
+                    add(&counters.errors, total_errors, &mut errors);
+                }
+                report @ Message::Report { .. } => reported = Some(report),
+            }
+        }
+
+        let status = child.0.wait()?;
+        let Some(Message::Report {
+            transactions,
+            retries,
+            failures,
+            attempt_latencies,
+            transaction_latencies,
+            errors,
+            status,
+            failure,
+        }) = reported
+        else {
+            return Err(anyhow::anyhow!("worker process {} exited with {status} before reporting", job.thread_id));
+        };
+        totals.transactions.fetch_add(transactions, Ordering::Relaxed);
+        totals.retries.fetch_add(retries, Ordering::Relaxed);
+        totals.failures.fetch_add(failures, Ordering::Relaxed);
+
+        Ok(Tally {
+            latencies: Latencies::from_recorded(&attempt_latencies, &transaction_latencies)?,
+            errors,
+            status,
+            failure,
+        })
+    })
+}
+
+/// Run the [`Job`] read from standard input and report to standard output. This is the `worker` subcommand.
+pub fn serve() -> Result<()> {
+    let mut job: Job = serde_json::from_reader(io::stdin().lock())?;
+    // sampled parameter values are not sent to the worker so read them again
+    job.workload.load(&Connection::open(&job.path)?)?;
+    // line up with the parent's clock so the measured window is the same in every process
+    let start = Instant::now() - SystemTime::now().duration_since(job.started).unwrap_or_default();
+    let finish_time = job.window.finish_time(start);
+    let totals = Arc::new(Totals::default());
+    let counters = Arc::new(Counters::default());
+
+    let reporter = {
+        let counters = counters.clone();
+        std::thread::spawn(move || -> Result<()> {
+            let mut stdout = io::stdout().lock();
+            while Instant::now() <= finish_time {
+                std::thread::sleep(PROGRESS_INTERVAL.min(finish_time.saturating_duration_since(Instant::now())));
+                writeln!(stdout, "{}", serde_json::to_string(&Message::progress(&counters))?)?;
+                stdout.flush()?;
+            }
+            Ok(())
+        })
+    };
+
+    let worker = Worker {
+        transaction_behavior: parse_behavior(&job.behavior)?,
+        path: job.path,
+        workload: Arc::new(job.workload),
+        mix: job.mix,
+        pragmas: job.pragmas,
+        retry_policy: job.retry_policy,
+        window: job.window,
+        start,
+        arrivals: job.arrivals,
+        checkpoint: None,
+        checkpoints: Arc::new(Checkpoints::new(job.window, start)?),
+        totals: totals.clone(),
+        counters: counters.clone(),
+    };
+    let keys = KeySampler::new(job.distribution, job.next_key)?
+        .for_thread(job.thread_id, job.n_threads)
+        .striped(job.thread_id, job.n_threads);
+    let Tally {
+        latencies,
+        errors,
+        status,
+        failure,
+    } = worker.run(job.thread_id, keys)?;
+    reporter.join().expect("should not fail")?;
+
+    let (attempt_latencies, transaction_latencies) = latencies.recorded();
+    let mut stdout = io::stdout().lock();
+    for message in [
+        Message::progress(&counters),
+        Message::Report {
+            transactions: totals.transactions.load(Ordering::Relaxed),
+            retries: totals.retries.load(Ordering::Relaxed),
+            failures: totals.failures.load(Ordering::Relaxed),
+            attempt_latencies,
+            transaction_latencies,
+            errors,
+            status,
+            failure,
+        },
+    ] {
+        writeln!(stdout, "{}", serde_json::to_string(&message)?)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,146 @@
+use anyhow::Result;
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr, time::Duration};
+
+/// How long to wait before retrying a busy transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Backoff {
+    /// Retry straight away.
+    Immediate,
+    /// Sleep for `delay_ms` before every retry.
+    Fixed { delay_ms: u64 },
+    /// Sleep for a random duration up to `base_ms * 2^(attempt - 1)`, capped at `max_ms`.
+    Exponential { base_ms: u64, max_ms: u64 },
+}
+
+impl fmt::Display for Backoff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Backoff::Immediate => write!(f, "immediate"),
+            Backoff::Fixed { delay_ms } => write!(f, "fixed:{delay_ms}"),
+            Backoff::Exponential { base_ms, max_ms } => write!(f, "exponential:{base_ms}:{max_ms}"),
+        }
+    }
+}
+
+impl FromStr for Backoff {
+    type Err = anyhow::Error;
+
+    /// Parse `immediate`, `fixed:<delay_ms>` or `exponential:<base_ms>:<max_ms>`.
+    fn from_str(s: &str) -> Result<Self> {
+        match s.split(':').collect::<Vec<_>>().as_slice() {
+            ["immediate"] => Ok(Backoff::Immediate),
+            ["fixed", delay_ms] => Ok(Backoff::Fixed { delay_ms: delay_ms.parse()? }),
+            ["exponential", base_ms, max_ms] => Ok(Backoff::Exponential {
+                base_ms: base_ms.parse()?,
+                max_ms: max_ms.parse()?,
+            }),
+            _ => Err(anyhow::anyhow!("unknown backoff {s:?}")),
+        }
+    }
+}
+
+/// When and how to retry a transaction that failed because the database was busy.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RetryPolicy {
+    pub backoff: Backoff,
+    /// Give up after this many attempts.
+    pub max_attempts: Option<u32>,
+    /// Give up once this many milliseconds have passed since the first attempt.
+    pub deadline_ms: Option<u64>,
+}
+
+impl RetryPolicy {
+    /// The delay before the next attempt after `attempts` have failed, or `None` if the transaction should be abandoned.
+    pub fn next_delay<R: Rng + ?Sized>(&self, attempts: u32, elapsed: Duration, rng: &mut R) -> Option<Duration> {
+        if self.max_attempts.is_some_and(|max_attempts| attempts >= max_attempts) {
+            return None;
+        }
+
+        let remaining = match self.deadline_ms {
+            Some(deadline_ms) => Duration::from_millis(deadline_ms)
+                .checked_sub(elapsed)
+                .filter(|remaining| !remaining.is_zero())?,
+            None => Duration::MAX,
+        };
+
+        let delay = match self.backoff {
+            Backoff::Immediate => Duration::ZERO,
+            Backoff::Fixed { delay_ms } => Duration::from_millis(delay_ms),
+            Backoff::Exponential { base_ms, max_ms } => {
+                let ceiling = base_ms.saturating_mul(1u64 << attempts.saturating_sub(1).min(63)).min(max_ms);
+                Duration::from_millis(rng.gen_range(0..=ceiling))
+            }
+        };
+
+        Some(delay.min(remaining))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(backoff: Backoff, max_attempts: Option<u32>, deadline_ms: Option<u64>) -> RetryPolicy {
+        RetryPolicy {
+            backoff,
+            max_attempts,
+            deadline_ms,
+        }
+    }
+
+    #[test]
+    fn stops_at_max_attempts() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(0);
+        let policy = policy(Backoff::Fixed { delay_ms: 5 }, Some(3), None);
+        assert_eq!(policy.next_delay(1, Duration::ZERO, &mut rng), Some(Duration::from_millis(5)));
+        assert_eq!(policy.next_delay(2, Duration::ZERO, &mut rng), Some(Duration::from_millis(5)));
+        assert_eq!(policy.next_delay(3, Duration::ZERO, &mut rng), None);
+        assert_eq!(policy.next_delay(4, Duration::ZERO, &mut rng), None);
+    }
+
+    #[test]
+    fn stops_at_the_deadline() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(0);
+        let policy = policy(Backoff::Fixed { delay_ms: 50 }, None, Some(100));
+        assert_eq!(policy.next_delay(1, Duration::from_millis(10), &mut rng), Some(Duration::from_millis(50)));
+        // the delay never sleeps past the deadline
+        assert_eq!(policy.next_delay(2, Duration::from_millis(80), &mut rng), Some(Duration::from_millis(20)));
+        assert_eq!(policy.next_delay(3, Duration::from_millis(100), &mut rng), None);
+        assert_eq!(policy.next_delay(4, Duration::from_millis(150), &mut rng), None);
+    }
+
+    #[test]
+    fn caps_exponential_backoff() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(0);
+        let policy = policy(Backoff::Exponential { base_ms: 10, max_ms: 100 }, None, None);
+        for attempts in 1..100 {
+            let ceiling = Duration::from_millis(10u64.saturating_mul(1 << (attempts - 1).min(63)).min(100));
+            for _ in 0..100 {
+                let delay = policy.next_delay(attempts, Duration::ZERO, &mut rng).unwrap();
+                assert!(delay <= ceiling, "{delay:?} after {attempts} attempts");
+            }
+        }
+        // the ceiling is reached once the doubling passes it
+        assert!((0..1000).any(|_| policy.next_delay(10, Duration::ZERO, &mut rng).unwrap() > Duration::from_millis(80)));
+    }
+
+    #[test]
+    fn retries_immediately_without_limits() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(0);
+        let policy = policy(Backoff::Immediate, None, None);
+        assert_eq!(policy.next_delay(1_000, Duration::from_secs(60 * 60), &mut rng), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn parses_what_it_displays() {
+        for s in ["immediate", "fixed:5", "exponential:1:100"] {
+            assert_eq!(s.parse::<Backoff>().unwrap().to_string(), s);
+        }
+        for s in ["", "fixed", "fixed:-1", "exponential:1", "linear:1"] {
+            assert!(s.parse::<Backoff>().is_err(), "{s:?}");
+        }
+    }
+}
@@ -0,0 +1,875 @@
+use crate::{
+    checkpoint::{self, Checkpoint, CheckpointMetrics, Checkpointed, Checkpoints, Mark},
+    distribution::{KeyDistribution, KeySampler},
+    errors::{Errors, Phase},
+    latency::{Latencies, Percentiles},
+    pragma::Pragmas,
+    process::{self, Job},
+    retry::RetryPolicy,
+    series::{self, Counters, FileSizes, Sample},
+    status::Status,
+    tasks,
+    workload::{Mix, Workload},
+};
+use anyhow::Result;
+use clap::ValueEnum;
+use rand::prelude::*;
+use rusqlite::{params_from_iter, types::Value, Connection, ErrorCode, OpenFlags, TransactionBehavior};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    ops::Add,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant, SystemTime},
+};
+
+/// The time windows of a single iteration. Transactions run for the whole window but are only counted during `duration`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Window {
+    pub warmup: Duration,
+    pub duration: Duration,
+    pub cooldown: Duration,
+}
+
+impl Window {
+    pub fn measure_start(&self, start: Instant) -> Instant {
+        start.add(self.warmup)
+    }
+
+    pub fn measure_end(&self, start: Instant) -> Instant {
+        self.measure_start(start).add(self.duration)
+    }
+
+    pub fn finish_time(&self, start: Instant) -> Instant {
+        self.measure_end(start).add(self.cooldown)
+    }
+
+    /// Whether `instant` falls within the measured part of a window beginning at `start`.
+    pub fn is_measured(&self, start: Instant, instant: Instant) -> bool {
+        self.measure_start(start) <= instant && instant <= self.measure_end(start)
+    }
+}
+
+/// An open loop schedule starting `rate` transactions per second across `n_threads` workers, whether or not earlier
+/// transactions have finished. Worker `thread_id` is due to start its `n`th transaction at
+/// `(n * n_threads + thread_id) / rate` seconds into the iteration.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Arrivals {
+    pub rate: u64,
+    pub n_threads: usize,
+}
+
+impl Arrivals {
+    pub fn due(&self, start: Instant, thread_id: usize, n: u64) -> Instant {
+        start + Duration::from_secs_f64((n as f64 * self.n_threads as f64 + thread_id as f64) / self.rate as f64)
+    }
+}
+
+/// The configuration of a single benchmark iteration.
+pub struct Iteration {
+    pub n_threads: usize,
+    pub mix: Mix,
+    pub distribution: KeyDistribution,
+    pub pragmas: Pragmas,
+    pub retry_policy: RetryPolicy,
+    pub transaction_behavior: TransactionBehavior,
+    /// Reader threads to run alongside the `n_threads` writers.
+    pub readers: Option<Readers>,
+    pub mode: Mode,
+    /// Connections in the pool of each role in [`Mode::Async`]. Defaults to one per task.
+    pub pool_size: Option<usize>,
+    /// Transactions per second the writers start open loop, measuring latency from when each was due. Writers run
+    /// closed loop, starting the next transaction once the last finished, if not set.
+    pub arrival_rate: Option<u64>,
+    /// How the WAL is checkpointed. Left to the worker connections' `wal_autocheckpoint`, unmeasured, if not set.
+    pub checkpoint: Option<Checkpoint>,
+}
+
+/// How workers are run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum Mode {
+    /// Threads of this process, sharing one SQLite library instance.
+    #[default]
+    Thread,
+    /// Processes re-executing the current binary with the hidden `worker` subcommand, locking through the file system.
+    Process,
+    /// Tasks on a tokio runtime, checking a connection out of a bounded pool and running each attempt with
+    /// `spawn_blocking`.
+    Async,
+}
+
+/// A pool of reader threads with its own operation counts and transaction behavior. Readers only run the operations
+/// the workload declares `reader_counts` for.
+#[derive(Clone)]
+pub struct Readers {
+    pub n_threads: usize,
+    pub mix: Mix,
+    pub transaction_behavior: TransactionBehavior,
+}
+
+/// Transaction behaviors accepted by [`parse_behavior`].
+pub const BEHAVIORS: [&str; 4] = ["deferred", "immediate", "exclusive", "concurrent"];
+
+/// The name of a transaction behavior as used in `BEGIN <behavior>`.
+pub fn behavior_name(behavior: TransactionBehavior) -> &'static str {
+    match behavior {
+        TransactionBehavior::Deferred => "DEFERRED",
+        TransactionBehavior::Immediate => "IMMEDIATE",
+        TransactionBehavior::Exclusive => "EXCLUSIVE",
+        TransactionBehavior::Concurrent => "CONCURRENT",
+        _ => unreachable!(),
+    }
+}
+
+/// Parse a transaction behavior name, ignoring case.
+pub fn parse_behavior(s: &str) -> Result<TransactionBehavior> {
+    match s.to_ascii_uppercase().as_str() {
+        "DEFERRED" => Ok(TransactionBehavior::Deferred),
+        "IMMEDIATE" => Ok(TransactionBehavior::Immediate),
+        "EXCLUSIVE" => Ok(TransactionBehavior::Exclusive),
+        "CONCURRENT" => Ok(TransactionBehavior::Concurrent),
+        _ => Err(anyhow::anyhow!("unknown transaction behavior {s:?}")),
+    }
+}
+
+/// Check that the SQLite build supports beginning a transaction with `behavior`.
+pub fn check_behavior(behavior: TransactionBehavior) -> Result<()> {
+    let mut conn = Connection::open_in_memory()?;
+    conn.transaction_with_behavior(behavior)
+        .map_err(|err| anyhow::anyhow!("BEGIN {} is not supported by this SQLite build: {err}", behavior_name(behavior)))?;
+
+    Ok(())
+}
+
+/// The count of every operation of `workload` in `mix`, including the ones it leaves out.
+fn operations(workload: &Workload, mix: &Mix) -> BTreeMap<String, usize> {
+    workload
+        .operations
+        .iter()
+        .map(|operation| (operation.name.clone(), mix.get(&operation.name).copied().unwrap_or(0)))
+        .collect()
+}
+
+impl Iteration {
+    pub fn configuration(&self, seed: Option<usize>, window: Window, workload: &Workload) -> Configuration {
+        Configuration {
+            behavior: behavior_name(self.transaction_behavior).to_string(),
+            seed,
+            duration_secs: window.duration.as_secs(),
+            workload: workload.name.clone(),
+            n_threads: self.n_threads,
+            operations: operations(workload, &self.mix),
+            distribution: self.distribution,
+            pragmas: match self.checkpoint {
+                Some(checkpoint) => Pragmas {
+                    wal_autocheckpoint: checkpoint.wal_autocheckpoint(),
+                    ..self.pragmas.clone()
+                },
+                None => self.pragmas.clone(),
+            },
+            retry_policy: self.retry_policy,
+            readers: self.readers.as_ref().map(|readers| ReadersConfiguration {
+                n_threads: readers.n_threads,
+                behavior: behavior_name(readers.transaction_behavior).to_string(),
+                operations: operations(workload, &readers.mix),
+            }),
+            mode: self.mode,
+            pool_size: self.pool_size,
+            arrival_rate: self.arrival_rate,
+            checkpoint: self.checkpoint,
+        }
+    }
+}
+
+/// The fields identifying the configuration a result was measured with.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Configuration {
+    pub behavior: String,
+    /// The number of seeded rows. Unset when benchmarking an existing database.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<usize>,
+    pub duration_secs: u64,
+    pub workload: String,
+    pub n_threads: usize,
+    pub operations: BTreeMap<String, usize>,
+    pub distribution: KeyDistribution,
+    pub pragmas: Pragmas,
+    pub retry_policy: RetryPolicy,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub readers: Option<ReadersConfiguration>,
+    #[serde(default)]
+    pub mode: Mode,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pool_size: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arrival_rate: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checkpoint: Option<Checkpoint>,
+}
+
+/// The configuration of the reader threads of an iteration.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReadersConfiguration {
+    pub n_threads: usize,
+    pub behavior: String,
+    pub operations: BTreeMap<String, usize>,
+}
+
+/// Throughput, retries and latencies measured over the threads of one pool.
+#[derive(Debug, Serialize)]
+pub struct Metrics {
+    pub retries: usize,
+    pub failures: usize,
+    pub transactions: usize,
+    pub errors: Errors,
+    pub tps: u128,
+    pub attempt_latency_us: Percentiles,
+    pub transaction_latency_us: Percentiles,
+    /// Time spent waiting for a pooled connection before each attempt in [`Mode::Async`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pool_wait_us: Option<Percentiles>,
+    /// Latency of the transactions that ran while a WAL checkpoint did, when the checkpoint strategy is set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transaction_latency_during_checkpoint_us: Option<Percentiles>,
+    /// SQLite status counters summed over the connections of the pool.
+    pub status: Status,
+}
+
+/// The result of a single benchmark iteration.
+#[derive(Debug, Serialize)]
+pub struct Transactions {
+    #[serde(flatten)]
+    pub configuration: Configuration,
+    /// The metrics of the `n_threads` writer threads.
+    #[serde(flatten)]
+    pub metrics: Metrics,
+    /// The metrics of the reader threads, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reader_metrics: Option<Metrics>,
+    /// The first error other than busy of any worker, or the error that stopped one, if any.
+    pub failure: Option<String>,
+    /// The table size at the end of the iteration, when the workload changes it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rows: Option<u64>,
+    /// The largest database file sizes sampled during the iteration or at its end.
+    pub peak_file_sizes: FileSizes,
+    /// The database file sizes when the iteration finished, before the workers closed their connections.
+    pub final_file_sizes: FileSizes,
+    /// The WAL checkpoints within the measured window, when the checkpoint strategy is set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checkpoints: Option<CheckpointMetrics>,
+    /// Activity of all threads over time.
+    pub series: Vec<Sample>,
+}
+
+/// Create the database at `path` in WAL mode and populate it with the workload schema.
+pub fn seed(path: &Path, rows: usize, workload: &Workload) -> Result<()> {
+    if workload.schema.trim().is_empty() {
+        return Err(anyhow::anyhow!(
+            "workload {:?} has no schema to seed, use --database to benchmark an existing database",
+            workload.name
+        ));
+    }
+    let conn = Connection::open(path)?;
+    conn.execute_batch(
+        "
+        PRAGMA journal_mode = WAL;
+        PRAGMA mmap_size = 1000000000;
+        PRAGMA synchronous = off;
+        PRAGMA journal_size_limit = 16777216;
+        ",
+    )?;
+    conn.execute_batch(&workload.schema.replace("{rows}", &rows.to_string()))?;
+
+    Ok(())
+}
+
+/// Copy the existing database at `source` and its WAL, if any, to `path` to benchmark in place of a seeded one. The
+/// copy is switched to WAL mode and the original is left untouched.
+pub fn import(source: &Path, path: &Path) -> Result<()> {
+    fs::copy(source, path)?;
+    if sibling(source, "-wal").exists() {
+        fs::copy(sibling(source, "-wal"), sibling(path, "-wal"))?;
+    }
+    Connection::open(path)?.query_row("PRAGMA journal_mode = WAL;", [], |_| Ok(()))?;
+
+    Ok(())
+}
+
+/// Checkpoint the whole WAL of the seeded or imported database at `path` into its main file and copy it to `template`, so every
+/// iteration can start from the same database with [`restore`].
+pub fn save_template(path: &Path, template: &Path) -> Result<()> {
+    let conn = Connection::open(path)?;
+    conn.query_row("PRAGMA wal_checkpoint(TRUNCATE);", [], |_| Ok(()))?;
+    conn.close().map_err(|(_, err)| err)?;
+    fs::copy(path, template)?;
+
+    Ok(())
+}
+
+/// Replace the database at `path` and its WAL with a copy of `template`.
+pub fn restore(template: &Path, path: &Path) -> Result<()> {
+    remove(path);
+    fs::copy(template, path)?;
+
+    Ok(())
+}
+
+/// Remove the database at `path` with its `-wal` and `-shm` files, ignoring any that do not exist.
+pub fn remove(path: &Path) {
+    for suffix in ["", "-wal", "-shm"] {
+        fs::remove_file(sibling(path, suffix)).ok();
+    }
+}
+
+/// The path of the file next to the database at `path` with `suffix` appended, such as its `-wal` file.
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut file = path.as_os_str().to_owned();
+    file.push(suffix);
+    PathBuf::from(file)
+}
+
+pub(crate) type WorkerHandle = JoinHandle<Result<Tally>>;
+
+/// Transactions, retries and failures measured across the workers of a pool.
+#[derive(Debug, Default)]
+pub(crate) struct Totals {
+    pub transactions: AtomicUsize,
+    pub retries: AtomicUsize,
+    pub failures: AtomicUsize,
+}
+
+/// Workers running the same transactions and the totals measured over them.
+struct Pool {
+    n_threads: usize,
+    mix: Vec<usize>,
+    transaction_behavior: TransactionBehavior,
+    arrivals: Option<Arrivals>,
+    totals: Arc<Totals>,
+}
+
+impl Pool {
+    fn new(n_threads: usize, mix: Vec<usize>, transaction_behavior: TransactionBehavior, arrivals: Option<Arrivals>) -> Self {
+        Self {
+            n_threads,
+            mix,
+            transaction_behavior,
+            arrivals,
+            totals: Arc::new(Totals::default()),
+        }
+    }
+
+    /// Wait for the workers of this pool and combine their measurements. The first failure is kept in `failure`.
+    fn join(&self, threads: Vec<WorkerHandle>, window: Window, pooled: bool, checkpointed: bool, failure: &mut Option<String>) -> Result<Metrics> {
+        let mut tally = Tally::new()?;
+        for thread in threads {
+            match thread.join() {
+                Ok(Ok(thread_tally)) => tally.add(thread_tally)?,
+                Ok(Err(err)) => tally.failure = tally.failure.take().or(Some(err.to_string())),
+                Err(_) => tally.failure = tally.failure.take().or(Some("worker thread panicked".to_string())),
+            }
+        }
+        *failure = failure.take().or(tally.failure);
+
+        let transactions = self.totals.transactions.load(Ordering::Relaxed);
+        Ok(Metrics {
+            retries: self.totals.retries.load(Ordering::Relaxed),
+            failures: self.totals.failures.load(Ordering::Relaxed),
+            transactions,
+            errors: tally.errors,
+            tps: (transactions as f64 / window.duration.as_secs_f64()) as u128,
+            attempt_latency_us: Percentiles::from(&tally.latencies.attempts),
+            transaction_latency_us: Percentiles::from(&tally.latencies.transactions),
+            pool_wait_us: pooled.then(|| Percentiles::from(&tally.latencies.pool_waits)),
+            transaction_latency_during_checkpoint_us: checkpointed.then(|| Percentiles::from(&tally.latencies.during_checkpoints)),
+            status: tally.status,
+        })
+    }
+}
+
+/// How far an attempt got before it committed or failed.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Step {
+    Begin,
+    /// Running the operation at this index of the workload.
+    Operation(usize),
+    Commit,
+}
+
+impl Step {
+    fn phase(self, workload: &Workload) -> Phase<'_> {
+        match self {
+            Step::Begin => Phase::Begin,
+            Step::Operation(index) => Phase::Operation(&workload.operations[index].name),
+            Step::Commit => Phase::Commit,
+        }
+    }
+}
+
+/// When a transaction was due to start and when its first attempt did. They only differ when an open loop worker
+/// falls behind its [`Arrivals`].
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Start {
+    pub due: Instant,
+    pub first_attempt: Instant,
+    /// The checkpoints as the first attempt started.
+    pub checkpoints: Mark,
+}
+
+/// What a worker does after an attempt.
+pub(crate) enum Outcome {
+    /// Try the same transaction again after the delay.
+    Retry(Duration),
+    /// Move on to the next transaction.
+    Done,
+    /// Stop running transactions after an error that leaves the connection unusable.
+    Stop,
+}
+
+/// What a worker has measured so far: its latencies, errors, connection status and its first error other than busy, if
+/// any.
+pub(crate) struct Tally {
+    pub latencies: Latencies,
+    pub errors: Errors,
+    pub status: Status,
+    pub failure: Option<String>,
+}
+
+impl Tally {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            latencies: Latencies::new()?,
+            errors: Errors::default(),
+            status: Status::default(),
+            failure: None,
+        })
+    }
+
+    /// Combine the measurements of another worker, keeping the first failure.
+    pub fn add(&mut self, other: Tally) -> Result<()> {
+        self.latencies.add(&other.latencies)?;
+        self.errors.add(other.errors);
+        self.status.add(other.status);
+        self.failure = self.failure.take().or(other.failure);
+        Ok(())
+    }
+}
+
+/// The transactions a worker thread, process or task runs and where it counts their outcomes.
+#[derive(Clone)]
+pub(crate) struct Worker {
+    pub path: PathBuf,
+    pub workload: Arc<Workload>,
+    pub mix: Vec<usize>,
+    pub transaction_behavior: TransactionBehavior,
+    pub pragmas: Pragmas,
+    pub retry_policy: RetryPolicy,
+    pub window: Window,
+    pub start: Instant,
+    /// The open loop schedule of the transactions, if any.
+    pub arrivals: Option<Arrivals>,
+    pub checkpoint: Option<Checkpoint>,
+    /// Counted by the checkpoints of every connection.
+    pub checkpoints: Arc<Checkpoints>,
+    /// Counted while measured.
+    pub totals: Arc<Totals>,
+    /// Counted throughout for the time series.
+    pub counters: Arc<Counters>,
+}
+
+impl Worker {
+    pub fn connect(&self) -> Result<Checkpointed> {
+        let conn = Connection::open_with_flags(&self.path, OpenFlags::SQLITE_OPEN_READ_WRITE)?;
+        conn.busy_timeout(Duration::from_millis(5000))?;
+        self.pragmas.apply(&conn)?;
+        Checkpointed::new(conn, self.checkpoint, &self.checkpoints)
+    }
+
+    /// The status counters of `conn` and of the statements of the operations this worker runs.
+    pub fn status(&self, conn: &Connection) -> Result<Status> {
+        let operations = self.workload.operations.iter().zip(&self.mix);
+        Status::of(conn, operations.filter(|(_, count)| **count != 0).map(|(operation, _)| operation))
+    }
+
+    pub fn finished(&self) -> bool {
+        Instant::now() > self.window.finish_time(self.start)
+    }
+
+    pub fn is_measured(&self) -> bool {
+        self.window.is_measured(self.start, Instant::now())
+    }
+
+    /// When the `n`th transaction of worker `thread_id` is due, if it runs open loop.
+    pub fn due(&self, thread_id: usize, n: u64) -> Option<Instant> {
+        self.arrivals.map(|arrivals| arrivals.due(self.start, thread_id, n))
+    }
+
+    /// Start a transaction due at `due`, or now if it runs closed loop.
+    pub fn start(&self, due: Option<Instant>) -> Start {
+        let first_attempt = Instant::now();
+        Start {
+            due: due.unwrap_or(first_attempt),
+            first_attempt,
+            checkpoints: self.checkpoints.mark(),
+        }
+    }
+
+    /// How long to wait before a transaction due at `due` may start, never past the end of the window.
+    pub fn until(&self, due: Instant) -> Duration {
+        due.min(self.window.finish_time(self.start)).saturating_duration_since(Instant::now())
+    }
+
+    /// The parameters of every execution of every operation in the next transaction.
+    pub fn executions<R: Rng + ?Sized>(&self, rng: &mut R, keys: &mut KeySampler) -> Vec<Vec<Vec<Value>>> {
+        self.workload
+            .operations
+            .iter()
+            .zip(&self.mix)
+            .map(|(operation, count)| {
+                (0..*count)
+                    .map(|_| operation.params.iter().map(|param| param.sample(rng, keys)).collect::<Vec<_>>())
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    /// Run one attempt of a transaction, returning how far it got.
+    pub fn attempt(
+        workload: &Workload,
+        conn: &mut Connection,
+        transaction_behavior: TransactionBehavior,
+        executions: &[Vec<Vec<Value>>],
+    ) -> (Step, rusqlite::Result<()>) {
+        let mut step = Step::Begin;
+        let mut transaction = || {
+            let txn = conn.transaction_with_behavior(transaction_behavior)?;
+
+            for (index, (operation, params)) in workload.operations.iter().zip(executions).enumerate() {
+                if params.is_empty() {
+                    continue;
+                }
+
+                step = Step::Operation(index);
+                let mut statement = txn.prepare_cached(&operation.sql)?;
+                for params in params {
+                    // Consume the results
+                    let mut rows = statement.query(params_from_iter(params))?;
+                    while rows.next()?.is_some() {}
+                }
+            }
+
+            step = Step::Commit;
+            txn.commit()
+        };
+
+        let result = transaction();
+        (step, result)
+    }
+
+    /// Count the outcome of an attempt that took `attempt` after `attempts` attempts of a transaction that began at
+    /// `start`, and decide what to do next. Transaction latency is measured from when it was due and the retry deadline
+    /// from its first attempt.
+    pub fn settle<R: Rng + ?Sized>(
+        &self,
+        tally: &mut Tally,
+        (step, result): (Step, rusqlite::Result<()>),
+        attempts: u32,
+        attempt: Duration,
+        start: Start,
+        rng: &mut R,
+    ) -> Outcome {
+        let measured = self.is_measured();
+        if measured {
+            tally.latencies.record_attempt(attempt);
+        }
+
+        let phase = step.phase(&self.workload);
+        match result {
+            Err(err) if err.sqlite_error_code() == Some(ErrorCode::DatabaseBusy) => {
+                self.counters.retries.fetch_add(1, Ordering::Relaxed);
+                if measured {
+                    self.totals.retries.fetch_add(1, Ordering::Relaxed);
+                    tally.errors.record(phase, &err);
+                }
+
+                match self.retry_policy.next_delay(attempts, start.first_attempt.elapsed(), rng) {
+                    Some(delay) => Outcome::Retry(delay),
+                    None => {
+                        self.counters.failures.fetch_add(1, Ordering::Relaxed);
+                        if measured {
+                            self.totals.failures.fetch_add(1, Ordering::Relaxed);
+                        }
+                        Outcome::Done
+                    }
+                }
+            }
+            Ok(_) => {
+                self.counters.commits.fetch_add(1, Ordering::Relaxed);
+                if measured {
+                    tally.latencies.record_transaction(start.due.elapsed());
+                    if self.checkpoints.overlapped(start.checkpoints) {
+                        tally.latencies.record_during_checkpoint(start.due.elapsed());
+                    }
+                    self.totals.transactions.fetch_add(1, Ordering::Relaxed);
+                }
+                Outcome::Done
+            }
+            Err(err) => {
+                self.counters.errors.fetch_add(1, Ordering::Relaxed);
+                if measured {
+                    tally.errors.record(phase, &err);
+                }
+                tally.failure.get_or_insert_with(|| format!("{phase}: {err}"));
+                // the transaction is rolled back so only stop when the connection cannot run another one
+                match err.sqlite_error_code() {
+                    Some(ErrorCode::DatabaseCorrupt | ErrorCode::NotADatabase | ErrorCode::CannotOpen) => Outcome::Stop,
+                    _ => Outcome::Done,
+                }
+            }
+        }
+    }
+
+    /// Run transactions on a connection of its own until the window finishes.
+    pub fn run(&self, thread_id: usize, mut keys: KeySampler) -> Result<Tally> {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(thread_id as u64);
+        let mut conn = self.connect()?;
+        let mut tally = Tally::new()?;
+
+        'run: for n in 0.. {
+            let due = self.due(thread_id, n);
+            if let Some(due) = due {
+                std::thread::sleep(self.until(due));
+            }
+            if self.finished() {
+                break;
+            }
+            let executions = self.executions(&mut rng, &mut keys);
+
+            let start = self.start(due);
+            let mut attempts = 0;
+            loop {
+                attempts += 1;
+                let attempt_start = Instant::now();
+                let result = Self::attempt(&self.workload, &mut conn, self.transaction_behavior, &executions);
+                match self.settle(&mut tally, result, attempts, attempt_start.elapsed(), start, &mut rng) {
+                    Outcome::Retry(delay) => {
+                        if !delay.is_zero() {
+                            std::thread::sleep(delay);
+                        }
+                    }
+                    Outcome::Done => break,
+                    Outcome::Stop => break 'run,
+                }
+            }
+        }
+
+        tally.status = self.status(&conn)?;
+        Ok(tally)
+    }
+}
+
+/// Run one iteration of `workload` against the database at `path`, seeded with `seed` rows or an existing one if `None`,
+/// sampling throughput every `interval`.
+pub fn begin(
+    path: &Path,
+    seed: Option<usize>,
+    window: Window,
+    interval: Duration,
+    workload: &Arc<Workload>,
+    iteration: Iteration,
+) -> Result<Transactions> {
+    let configuration = iteration.configuration(seed, window, workload);
+    let Iteration {
+        n_threads,
+        mix,
+        distribution,
+        pragmas,
+        retry_policy,
+        transaction_behavior,
+        readers,
+        mode,
+        pool_size,
+        arrival_rate,
+        checkpoint,
+    } = iteration;
+    if checkpoint.is_some() && mode == Mode::Process {
+        return Err(anyhow::anyhow!(
+            "checkpoint strategies require workers sharing this process, not --mode process"
+        ));
+    }
+    if window.duration.is_zero() {
+        return Err(anyhow::anyhow!("measured duration must be longer than zero"));
+    }
+    if pool_size == Some(0) {
+        return Err(anyhow::anyhow!("connection pool size must be at least 1"));
+    }
+    if arrival_rate == Some(0) {
+        return Err(anyhow::anyhow!("arrival rate must be at least 1 transaction per second"));
+    }
+    if interval.is_zero() {
+        return Err(anyhow::anyhow!("sample interval must be at least 1 ms"));
+    }
+    if let Some(Checkpoint::Background { interval_ms: 0, .. }) = checkpoint {
+        return Err(anyhow::anyhow!("background checkpoint interval must be at least 1 ms"));
+    }
+    let mix = workload.counts(&mix)?;
+    let next_key = match (&workload.next_key, seed) {
+        (Some(query), _) => Connection::open(path)?.query_row(query, [], |row| row.get::<_, i64>(0))? as u64,
+        (None, Some(seed)) => seed as u64 + 1,
+        (None, None) => {
+            return Err(anyhow::anyhow!(
+                "workload {:?} needs a next_key query to draw keys from a database it did not seed",
+                workload.name
+            ))
+        }
+    };
+    let keys = KeySampler::new(distribution, next_key)?;
+    // only pay for counting rows when the mix actually changes the table size
+    let size = workload.size.clone().filter(|_| {
+        workload
+            .operations
+            .iter()
+            .zip(&mix)
+            .any(|(operation, count)| operation.resizes && *count != 0)
+    });
+    let conn = Connection::open(path)?;
+    let page_size = conn.pragma_query_value(None, "page_size", |row| row.get::<_, i64>(0))? as u64;
+    let arrivals = arrival_rate.map(|rate| Arrivals { rate, n_threads });
+    let writers = Pool::new(n_threads, mix, transaction_behavior, arrivals);
+    let readers = match readers {
+        Some(readers) => Some(Pool::new(
+            readers.n_threads,
+            workload.counts(&readers.mix)?,
+            readers.transaction_behavior,
+            None,
+        )),
+        None => None,
+    };
+    let total_threads = n_threads + readers.as_ref().map_or(0, |readers| readers.n_threads);
+    let counters = Arc::new(Counters::default());
+    let start = Instant::now();
+    let started = SystemTime::now();
+    let sampler = series::spawn(
+        counters.clone(),
+        start,
+        window.finish_time(start),
+        interval,
+        path.to_path_buf(),
+        page_size,
+        size.clone(),
+    );
+    let checkpoints = Arc::new(Checkpoints::new(window, start)?);
+    let checkpointer = match checkpoint {
+        Some(Checkpoint::Background {
+            mode: checkpoint_mode,
+            interval_ms,
+        }) => Some(checkpoint::spawn(
+            path.to_path_buf(),
+            checkpoint_mode,
+            Duration::from_millis(interval_ms),
+            checkpoints.clone(),
+            window.finish_time(start),
+        )),
+        _ => None,
+    };
+    // worker ids are unique across pools so every worker gets its own rng seed and sequential offset
+    let spawn = |pool: &Pool, first_id: usize| -> Vec<WorkerHandle> {
+        let worker = Worker {
+            path: path.to_path_buf(),
+            workload: workload.clone(),
+            mix: pool.mix.clone(),
+            transaction_behavior: pool.transaction_behavior,
+            pragmas: pragmas.clone(),
+            retry_policy,
+            window,
+            start,
+            arrivals: pool.arrivals,
+            checkpoint,
+            checkpoints: checkpoints.clone(),
+            totals: pool.totals.clone(),
+            counters: counters.clone(),
+        };
+        let ids = first_id..first_id + pool.n_threads;
+        match mode {
+            Mode::Thread => ids
+                .map(|thread_id| {
+                    let worker = worker.clone();
+                    let keys = keys.clone().for_thread(thread_id, total_threads);
+                    std::thread::spawn(move || worker.run(thread_id, keys))
+                })
+                .collect(),
+            Mode::Process => ids
+                .map(|thread_id| {
+                    process::spawn(
+                        Job {
+                            path: path.to_path_buf(),
+                            workload: (**workload).clone(),
+                            mix: pool.mix.clone(),
+                            behavior: behavior_name(pool.transaction_behavior).to_string(),
+                            distribution,
+                            next_key,
+                            pragmas: pragmas.clone(),
+                            retry_policy,
+                            window,
+                            started,
+                            thread_id,
+                            n_threads: total_threads,
+                            arrivals: pool.arrivals,
+                        },
+                        worker.totals.clone(),
+                        worker.counters.clone(),
+                    )
+                })
+                .collect(),
+            Mode::Async => vec![tasks::spawn(
+                worker,
+                ids,
+                total_threads,
+                pool_size.unwrap_or(pool.n_threads),
+                keys.clone(),
+            )],
+        }
+    };
+    let writer_threads = spawn(&writers, 0);
+    let reader_threads = match &readers {
+        Some(readers) => spawn(readers, n_threads),
+        None => Vec::new(),
+    };
+
+    let mut failure = None;
+    let pooled = mode == Mode::Async;
+    let checkpointed = checkpoint.is_some();
+    let metrics = writers.join(writer_threads, window, pooled, checkpointed, &mut failure)?;
+    let reader_metrics = match &readers {
+        Some(readers) => Some(readers.join(reader_threads, window, pooled, checkpointed, &mut failure)?),
+        None => None,
+    };
+    if let Some(Err(err)) = checkpointer.map(|checkpointer| checkpointer.join().expect("should not fail")) {
+        failure = failure.or(Some(format!("checkpoint: {err}")));
+    }
+    let (series, final_file_sizes) = sampler.join().expect("should not fail");
+    let rows = match size {
+        Some(query) => series::rows(&conn, &query),
+        None => None,
+    };
+    let peak_file_sizes = series.iter().map(|sample| sample.files).fold(final_file_sizes, FileSizes::max);
+
+    Ok(Transactions {
+        configuration,
+        metrics,
+        reader_metrics,
+        failure,
+        rows,
+        peak_file_sizes,
+        final_file_sizes,
+        checkpoints: checkpointed.then(|| checkpoints.metrics()),
+        series,
+    })
+}
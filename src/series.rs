@@ -0,0 +1,133 @@
+use rusqlite::{Connection, OpenFlags};
+use serde::Serialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+/// Running totals over the whole iteration, including the warmup and cooldown windows.
+#[derive(Debug, Default)]
+pub struct Counters {
+    pub commits: AtomicUsize,
+    pub retries: AtomicUsize,
+    pub failures: AtomicUsize,
+    /// Errors other than the database being busy.
+    pub errors: AtomicUsize,
+}
+
+/// The activity during one sampling interval.
+#[derive(Debug, Serialize)]
+pub struct Sample {
+    /// Milliseconds since the start of the iteration at the end of the interval.
+    pub elapsed_ms: u64,
+    pub commits: usize,
+    pub retries: usize,
+    pub failures: usize,
+    pub errors: usize,
+    /// The table size at the end of the interval, when the workload changes it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rows: Option<u64>,
+    /// The database file sizes at the end of the interval.
+    #[serde(flatten)]
+    pub files: FileSizes,
+}
+
+/// The sizes of the database file and its `-wal` and `-shm` files. Missing files count as empty.
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct FileSizes {
+    pub db_bytes: u64,
+    pub wal_bytes: u64,
+    pub shm_bytes: u64,
+    pub db_pages: u64,
+    pub wal_frames: u64,
+}
+
+impl FileSizes {
+    /// The sizes of the files of the database at `path` with pages of `page_size` bytes.
+    pub fn of(path: &Path, page_size: u64) -> Self {
+        let bytes = |suffix: &str| {
+            let mut path = path.as_os_str().to_owned();
+            path.push(suffix);
+            fs::metadata(path).map_or(0, |metadata| metadata.len())
+        };
+        let (db_bytes, wal_bytes, shm_bytes) = (bytes(""), bytes("-wal"), bytes("-shm"));
+
+        Self {
+            db_bytes,
+            wal_bytes,
+            shm_bytes,
+            db_pages: db_bytes / page_size,
+            // a 32 byte header followed by frames of a 24 byte header and a page
+            wal_frames: wal_bytes.saturating_sub(32) / (page_size + 24),
+        }
+    }
+
+    /// The larger of each size.
+    pub fn max(self, other: FileSizes) -> Self {
+        Self {
+            db_bytes: self.db_bytes.max(other.db_bytes),
+            wal_bytes: self.wal_bytes.max(other.wal_bytes),
+            shm_bytes: self.shm_bytes.max(other.shm_bytes),
+            db_pages: self.db_pages.max(other.db_pages),
+            wal_frames: self.wal_frames.max(other.wal_frames),
+        }
+    }
+}
+
+/// Snapshot `counters` and the sizes of the files of the database at `path` every `interval` from `start` until
+/// `finish_time`, returning the samples and the file sizes at `finish_time`. When a `size` query is given the table size
+/// is sampled too.
+pub fn spawn(
+    counters: Arc<Counters>,
+    start: Instant,
+    finish_time: Instant,
+    interval: Duration,
+    path: PathBuf,
+    page_size: u64,
+    size: Option<String>,
+) -> JoinHandle<(Vec<Sample>, FileSizes)> {
+    std::thread::spawn(move || {
+        let size = size.and_then(|query| Some((Connection::open_with_flags(&path, OpenFlags::SQLITE_OPEN_READ_ONLY).ok()?, query)));
+        let mut samples = Vec::new();
+        let (mut commits, mut retries, mut failures, mut errors) = (0, 0, 0, 0);
+
+        let mut next = start + interval;
+        while next <= finish_time {
+            std::thread::sleep(next.saturating_duration_since(Instant::now()));
+
+            let (total_commits, total_retries, total_failures, total_errors) = (
+                counters.commits.load(Ordering::Relaxed),
+                counters.retries.load(Ordering::Relaxed),
+                counters.failures.load(Ordering::Relaxed),
+                counters.errors.load(Ordering::Relaxed),
+            );
+            samples.push(Sample {
+                elapsed_ms: next.duration_since(start).as_millis() as u64,
+                commits: total_commits - commits,
+                retries: total_retries - retries,
+                failures: total_failures - failures,
+                errors: total_errors - errors,
+                rows: size.as_ref().and_then(|(conn, query)| rows(conn, query)),
+                files: FileSizes::of(&path, page_size),
+            });
+            (commits, retries, failures, errors) = (total_commits, total_retries, total_failures, total_errors);
+
+            next += interval;
+        }
+
+        // taken before the workers stop, as the last connection to close checkpoints and removes the WAL
+        std::thread::sleep(finish_time.saturating_duration_since(Instant::now()));
+        (samples, FileSizes::of(&path, page_size))
+    })
+}
+
+/// Run the `size` query, ignoring failures such as the database being busy so sampling never stops an iteration.
+pub fn rows(conn: &Connection, query: &str) -> Option<u64> {
+    conn.query_row(query, [], |row| row.get::<_, i64>(0)).ok().map(|rows| rows as u64)
+}
@@ -0,0 +1,132 @@
+use crate::workload::Operation;
+use anyhow::Result;
+use rusqlite::{ffi, Connection, StatementStatus};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, os::raw::c_int};
+
+/// SQLite status counters summed over worker connections, read when each worker finishes so they include the warmup
+/// and cooldown windows.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Status {
+    pub connection: ConnectionStatus,
+    /// The counters of the prepared statement of each operation that was run.
+    pub statements: BTreeMap<String, StatementCounters>,
+}
+
+impl Status {
+    /// The counters of `conn` and of its cached statements for `operations`.
+    pub fn of<'a>(conn: &Connection, operations: impl IntoIterator<Item = &'a Operation>) -> Result<Self> {
+        Ok(Self {
+            connection: ConnectionStatus::of(conn)?,
+            statements: operations
+                .into_iter()
+                .map(|operation| Ok((operation.name.clone(), StatementCounters::of(conn, &operation.sql)?)))
+                .collect::<Result<_>>()?,
+        })
+    }
+
+    pub fn add(&mut self, other: Status) {
+        self.connection.add(&other.connection);
+        for (name, counters) in other.statements {
+            self.statements.entry(name).or_default().add(&counters);
+        }
+    }
+}
+
+/// The `sqlite3_db_status` counters of a connection. Cache and lookaside hits and misses count from when the connection
+/// opened, the `_used` values are the memory or lookaside slots in use when read.
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct ConnectionStatus {
+    pub cache_hit: i64,
+    pub cache_miss: i64,
+    pub cache_write: i64,
+    pub cache_spill: i64,
+    pub cache_used_bytes: i64,
+    pub lookaside_used: i64,
+    pub lookaside_hit: i64,
+    pub lookaside_miss_size: i64,
+    pub lookaside_miss_full: i64,
+    pub schema_used_bytes: i64,
+    pub stmt_used_bytes: i64,
+}
+
+impl ConnectionStatus {
+    pub fn of(conn: &Connection) -> Result<Self> {
+        // the current value and the highwater mark of a counter
+        let status = |op: c_int| -> Result<(i64, i64)> {
+            let (mut current, mut highwater) = (0, 0);
+            // SAFETY: the handle stays valid while `conn` is borrowed and SQLite only writes the two integers
+            let rc = unsafe { ffi::sqlite3_db_status(conn.handle(), op, &mut current, &mut highwater, 0) };
+            if rc != ffi::SQLITE_OK {
+                return Err(anyhow::anyhow!("sqlite3_db_status({op}) failed with {rc}"));
+            }
+            Ok((current as i64, highwater as i64))
+        };
+
+        Ok(Self {
+            cache_hit: status(ffi::SQLITE_DBSTATUS_CACHE_HIT)?.0,
+            cache_miss: status(ffi::SQLITE_DBSTATUS_CACHE_MISS)?.0,
+            cache_write: status(ffi::SQLITE_DBSTATUS_CACHE_WRITE)?.0,
+            cache_spill: status(ffi::SQLITE_DBSTATUS_CACHE_SPILL)?.0,
+            cache_used_bytes: status(ffi::SQLITE_DBSTATUS_CACHE_USED)?.0,
+            lookaside_used: status(ffi::SQLITE_DBSTATUS_LOOKASIDE_USED)?.0,
+            // the lookaside hit and miss counts are reported as the highwater mark
+            lookaside_hit: status(ffi::SQLITE_DBSTATUS_LOOKASIDE_HIT)?.1,
+            lookaside_miss_size: status(ffi::SQLITE_DBSTATUS_LOOKASIDE_MISS_SIZE)?.1,
+            lookaside_miss_full: status(ffi::SQLITE_DBSTATUS_LOOKASIDE_MISS_FULL)?.1,
+            schema_used_bytes: status(ffi::SQLITE_DBSTATUS_SCHEMA_USED)?.0,
+            stmt_used_bytes: status(ffi::SQLITE_DBSTATUS_STMT_USED)?.0,
+        })
+    }
+
+    pub fn add(&mut self, other: &ConnectionStatus) {
+        self.cache_hit += other.cache_hit;
+        self.cache_miss += other.cache_miss;
+        self.cache_write += other.cache_write;
+        self.cache_spill += other.cache_spill;
+        self.cache_used_bytes += other.cache_used_bytes;
+        self.lookaside_used += other.lookaside_used;
+        self.lookaside_hit += other.lookaside_hit;
+        self.lookaside_miss_size += other.lookaside_miss_size;
+        self.lookaside_miss_full += other.lookaside_miss_full;
+        self.schema_used_bytes += other.schema_used_bytes;
+        self.stmt_used_bytes += other.stmt_used_bytes;
+    }
+}
+
+/// The `sqlite3_stmt_status` counters of a prepared statement since it was prepared.
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct StatementCounters {
+    pub fullscan_steps: i64,
+    pub sorts: i64,
+    pub autoindexes: i64,
+    pub vm_steps: i64,
+    pub reprepares: i64,
+    pub runs: i64,
+}
+
+impl StatementCounters {
+    /// The counters of the statement for `sql` in the statement cache of `conn`.
+    pub fn of(conn: &Connection, sql: &str) -> Result<Self> {
+        let statement = conn.prepare_cached(sql)?;
+        let status = |status: StatementStatus| statement.get_status(status) as i64;
+
+        Ok(Self {
+            fullscan_steps: status(StatementStatus::FullscanStep),
+            sorts: status(StatementStatus::Sort),
+            autoindexes: status(StatementStatus::AutoIndex),
+            vm_steps: status(StatementStatus::VmStep),
+            reprepares: status(StatementStatus::RePrepare),
+            runs: status(StatementStatus::Run),
+        })
+    }
+
+    pub fn add(&mut self, other: &StatementCounters) {
+        self.fullscan_steps += other.fullscan_steps;
+        self.sorts += other.sorts;
+        self.autoindexes += other.autoindexes;
+        self.vm_steps += other.vm_steps;
+        self.reprepares += other.reprepares;
+        self.runs += other.runs;
+    }
+}
@@ -0,0 +1,145 @@
+use crate::{
+    checkpoint::Checkpointed,
+    distribution::KeySampler,
+    runner::{Outcome, Tally, Worker, WorkerHandle},
+};
+use anyhow::Result;
+use rand::prelude::*;
+use std::{
+    ops::Range,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+use tokio::{sync::Semaphore, task::JoinHandle};
+
+/// A bounded pool of connections that tasks check out for each attempt.
+struct ConnectionPool {
+    connections: Mutex<Vec<Checkpointed>>,
+    available: Semaphore,
+}
+
+impl ConnectionPool {
+    fn new(connections: Vec<Checkpointed>) -> Self {
+        Self {
+            available: Semaphore::new(connections.len()),
+            connections: Mutex::new(connections),
+        }
+    }
+
+    /// Wait until a connection is free and take it out of the pool.
+    async fn get(&self) -> Result<Checkpointed> {
+        self.available.acquire().await?.forget();
+        Ok(self
+            .connections
+            .lock()
+            .expect("should not be poisoned")
+            .pop()
+            .expect("one connection per permit"))
+    }
+
+    fn put(&self, conn: Checkpointed) {
+        self.connections.lock().expect("should not be poisoned").push(conn);
+        self.available.add_permits(1);
+    }
+}
+
+/// Run one task per id in `ids` on a tokio runtime of its own, sharing a pool of `pool_size` connections. Every
+/// attempt waits for a pooled connection and then runs on the blocking thread pool.
+pub(crate) fn spawn(worker: Worker, ids: Range<usize>, n_threads: usize, pool_size: usize, keys: KeySampler) -> WorkerHandle {
+    std::thread::spawn(move || {
+        let connections = (0..pool_size).map(|_| worker.connect()).collect::<Result<Vec<_>>>()?;
+        let pool = Arc::new(ConnectionPool::new(connections));
+        let worker = Arc::new(worker);
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .max_blocking_threads(pool_size.max(1))
+            .enable_time()
+            .build()?;
+
+        let mut tally = runtime.block_on(async {
+            let tasks = ids
+                .map(|task_id| tokio::spawn(run(worker.clone(), pool.clone(), task_id, keys.clone().for_thread(task_id, n_threads))))
+                .collect();
+            join(tasks).await
+        })?;
+
+        // every connection is back in the pool once the tasks have finished
+        for conn in pool.connections.lock().expect("should not be poisoned").iter() {
+            tally.status.add(worker.status(conn)?);
+        }
+        Ok(tally)
+    })
+}
+
+/// Wait for the tasks and combine their measurements, keeping the first failure.
+async fn join(tasks: Vec<JoinHandle<Result<Tally>>>) -> Result<Tally> {
+    let mut tally = Tally::new()?;
+    for task in tasks {
+        match task.await {
+            Ok(Ok(task_tally)) => tally.add(task_tally)?,
+            Ok(Err(err)) => tally.failure = tally.failure.take().or(Some(err.to_string())),
+            Err(_) => tally.failure = tally.failure.take().or(Some("worker task panicked".to_string())),
+        }
+    }
+
+    Ok(tally)
+}
+
+/// Run transactions with connections from `pool` until the window finishes.
+async fn run(worker: Arc<Worker>, pool: Arc<ConnectionPool>, task_id: usize, mut keys: KeySampler) -> Result<Tally> {
+    let mut rng: StdRng = SeedableRng::seed_from_u64(task_id as u64);
+    let mut tally = Tally::new()?;
+
+    'run: for n in 0.. {
+        let due = worker.due(task_id, n);
+        if let Some(due) = due {
+            tokio::time::sleep(worker.until(due)).await;
+        }
+        if worker.finished() {
+            break;
+        }
+        let executions = Arc::new(worker.executions(&mut rng, &mut keys));
+
+        let start = worker.start(due);
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let wait_start = Instant::now();
+            let mut conn = pool.get().await?;
+            if worker.is_measured() {
+                tally.latencies.record_pool_wait(wait_start.elapsed());
+            }
+
+            let attempt_start = Instant::now();
+            let blocking = {
+                let worker = worker.clone();
+                let executions = executions.clone();
+                tokio::task::spawn_blocking(move || {
+                    let result = Worker::attempt(&worker.workload, &mut conn, worker.transaction_behavior, &executions);
+                    (conn, result)
+                })
+            };
+            let (conn, result) = match blocking.await {
+                Ok(attempted) => attempted,
+                Err(err) => {
+                    // the connection is lost with the panicked attempt so stop the other tasks waiting for it
+                    pool.available.close();
+                    return Err(err.into());
+                }
+            };
+            let attempt = attempt_start.elapsed();
+            pool.put(conn);
+
+            match worker.settle(&mut tally, result, attempts, attempt, start, &mut rng) {
+                Outcome::Retry(delay) => {
+                    if !delay.is_zero() {
+                        tokio::time::sleep(delay).await;
+                    }
+                }
+                Outcome::Done => break,
+                Outcome::Stop => break 'run,
+            }
+        }
+    }
+
+    Ok(tally)
+}
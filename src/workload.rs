@@ -0,0 +1,402 @@
+use crate::distribution::KeySampler;
+use anyhow::Result;
+use clap::ValueEnum;
+use itertools::Itertools;
+use rand::prelude::*;
+use rusqlite::{types::Value, Connection};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs, path::Path, sync::Arc};
+
+/// A workload to benchmark: the schema to seed and the operations each transaction performs.
+///
+/// ```toml
+/// name = "example"
+/// schema = """
+/// CREATE TABLE tbl(a INTEGER PRIMARY KEY, b BLOB(200));
+/// WITH RECURSIVE generate_series(value) AS (
+///     SELECT 0 UNION ALL SELECT value+1 FROM generate_series WHERE value < {rows}
+/// )
+/// INSERT INTO tbl SELECT value, randomblob(200) FROM generate_series;
+/// """
+///
+/// [[operations]]
+/// name = "update"
+/// sql = "UPDATE tbl SET b=? WHERE a=?;"
+/// counts = [1, 10]
+/// params = [
+///     { type = "blob", length = 200 },
+///     { type = "key" },
+/// ]
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Workload {
+    pub name: String,
+    /// Script run once to create and populate the database. `{rows}` is replaced with the seed row count and the
+    /// table is expected to hold keys `0..={rows}`. Not run when benchmarking an existing database.
+    #[serde(default)]
+    pub schema: String,
+    /// Query returning the lowest key that is free to insert. Defaults to one past the seeded keys.
+    pub next_key: Option<String>,
+    /// Query returning the number of rows, sampled over time when any operation `resizes` the table.
+    pub size: Option<String>,
+    pub operations: Vec<Operation>,
+}
+
+/// The number of executions per transaction of each operation by name. Operations left out are not run.
+pub type Mix = BTreeMap<String, usize>;
+
+/// A statement executed a number of times within each transaction.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Operation {
+    pub name: String,
+    pub sql: String,
+    #[serde(default)]
+    pub params: Vec<Param>,
+    /// Number of executions per transaction. Each value is a sweep dimension.
+    pub counts: Vec<usize>,
+    /// Number of executions per transaction on the reader threads. Each value is a sweep dimension. Operations
+    /// without any are not run by readers.
+    #[serde(default)]
+    pub reader_counts: Vec<usize>,
+    /// Whether the operation changes the number of rows, such as an insert or delete.
+    #[serde(default)]
+    pub resizes: bool,
+}
+
+/// A generator for a single statement parameter.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Param {
+    /// Random uppercase hexadecimal text.
+    Hex { length: usize },
+    /// Random bytes.
+    Blob { length: usize },
+    /// Uniformly distributed integer between `min` and `max` inclusive.
+    Integer { min: i64, max: i64 },
+    /// Row id drawn from the key distribution over the seeded and inserted rows.
+    Key,
+    /// Row id that has not been used before, shared between threads so inserts never collide.
+    NewKey,
+    /// Uppercase hexadecimal prefix drawn from the key distribution, spread evenly over the hexadecimal key space.
+    KeyPrefix { length: usize },
+    /// A value of the first column of `query` drawn from the key distribution over its rows, such as the keys or scan
+    /// prefixes of an existing database. The values are read by [`Workload::load`] and every row is held in memory by
+    /// each process, so large tables are better sampled in the query itself.
+    Sampled {
+        query: String,
+        #[serde(skip)]
+        values: Arc<Vec<Value>>,
+    },
+}
+
+struct Hexadecimal;
+impl Distribution<char> for Hexadecimal {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> char {
+        *b"0123456789ABCDEF".choose(rng).unwrap() as char
+    }
+}
+
+impl Param {
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R, keys: &mut KeySampler) -> Value {
+        match self {
+            Param::Hex { length } => Value::Text(rng.sample_iter(&Hexadecimal).take(*length).collect()),
+            Param::Blob { length } => {
+                let mut bytes = vec![0; *length];
+                rng.fill_bytes(&mut bytes);
+                Value::Blob(bytes)
+            }
+            Param::Integer { min, max } => Value::Integer(rng.gen_range(*min..=*max)),
+            Param::Key => Value::Integer(keys.sample(rng) as i64),
+            Param::NewKey => Value::Integer(keys.allocate() as i64),
+            Param::KeyPrefix { length } => {
+                let fraction = keys.fraction(rng);
+                let prefix = (fraction * 16f64.powi(*length as i32)) as u128;
+                Value::Text(format!("{prefix:0length$X}"))
+            }
+            Param::Sampled { values, .. } => {
+                let fraction = keys.fraction(rng);
+                values[((fraction * values.len() as f64) as usize).min(values.len() - 1)].clone()
+            }
+        }
+    }
+}
+
+/// The table layout of the builtin workload. Every variant runs the same logical operations.
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum Schema {
+    /// An integer primary key aliasing the rowid, with expression indexes on two prefixes of `c`.
+    #[default]
+    Rowid,
+    /// The rowid layout as a STRICT table, checking the type of every value written.
+    Strict,
+    /// A WITHOUT ROWID table keyed by the zero padded text of each key.
+    TextKey,
+    /// A WITHOUT ROWID table keyed by `(g, a)`, where the group `g` is the key modulo 16.
+    CompositeKey,
+    /// The rowid layout without secondary indexes, so scans read the whole table.
+    NoIndexes,
+    /// The rowid layout with the scan index covering `b` and `c`, so scans never read the table.
+    CoveringIndex,
+}
+
+impl Schema {
+    /// The name of the variant on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Schema::Rowid => "rowid",
+            Schema::Strict => "strict",
+            Schema::TextKey => "text-key",
+            Schema::CompositeKey => "composite-key",
+            Schema::NoIndexes => "no-indexes",
+            Schema::CoveringIndex => "covering-index",
+        }
+    }
+
+    /// The script creating and populating the table.
+    fn script(self) -> String {
+        let table = match self {
+            Schema::Strict => "CREATE TABLE tbl(a INTEGER PRIMARY KEY, b BLOB, c TEXT) STRICT;",
+            Schema::TextKey => "CREATE TABLE tbl(a TEXT PRIMARY KEY, b BLOB(200), c CHAR(64)) WITHOUT ROWID;",
+            Schema::CompositeKey => "CREATE TABLE tbl(g INTEGER, a INTEGER, b BLOB(200), c CHAR(64), PRIMARY KEY (g, a)) WITHOUT ROWID;",
+            Schema::Rowid | Schema::NoIndexes | Schema::CoveringIndex => "CREATE TABLE tbl(a INTEGER PRIMARY KEY, b BLOB(200), c CHAR(64));",
+        };
+        let key = match self {
+            Schema::TextKey => "printf('%016d', value)",
+            Schema::CompositeKey => "value % 16, value",
+            _ => "value",
+        };
+        let indexes = match self {
+            Schema::NoIndexes => "",
+            Schema::CoveringIndex => {
+                "
+                CREATE INDEX tbl_i1 ON tbl(substr(c, 1, 16), b, c);
+                CREATE INDEX tbl_i2 ON tbl(substr(c, 2, 16));"
+            }
+            _ => {
+                "
+                CREATE INDEX tbl_i1 ON tbl(substr(c, 1, 16));
+                CREATE INDEX tbl_i2 ON tbl(substr(c, 2, 16));"
+            }
+        };
+
+        format!(
+            "
+                {table}
+
+                -- https://www.sqlite.org/series.html
+                WITH RECURSIVE generate_series(value) AS (
+                    SELECT 0
+                    UNION ALL
+                    SELECT value+1 FROM generate_series
+                    WHERE value < {{rows}}
+                )
+                INSERT INTO tbl
+                SELECT {key}, randomblob(200), hex(randomblob(32))
+                FROM generate_series;
+                {indexes}
+            "
+        )
+    }
+
+    /// The expression of the stored primary key for the integer key in parameter `param`.
+    fn key(self, param: usize) -> String {
+        match self {
+            Schema::TextKey => format!("printf('%016d', ?{param})"),
+            Schema::CompositeKey => format!("?{param} % 16, ?{param}"),
+            _ => format!("?{param}"),
+        }
+    }
+
+    /// The condition selecting the row of the integer key in parameter `param`.
+    fn matches(self, param: usize) -> String {
+        match self {
+            Schema::CompositeKey => format!("g=?{param} % 16 AND a=?{param}"),
+            _ => format!("a={}", self.key(param)),
+        }
+    }
+
+    fn next_key(self) -> &'static str {
+        match self {
+            Schema::TextKey => "SELECT coalesce(CAST(max(a) AS INTEGER), -1) + 1 FROM tbl;",
+            _ => "SELECT coalesce(max(a), -1) + 1 FROM tbl;",
+        }
+    }
+}
+
+/// The numbers of executions per transaction of the operations of the builtin workload. Each value is a sweep
+/// dimension.
+#[derive(Debug, Clone)]
+pub struct BuiltinCounts {
+    pub scans: Vec<usize>,
+    pub lookups: Vec<usize>,
+    pub updates: Vec<usize>,
+    pub inserts: Vec<usize>,
+    pub deletes: Vec<usize>,
+    /// Scans on the reader threads.
+    pub reader_scans: Vec<usize>,
+    /// Lookups on the reader threads.
+    pub reader_lookups: Vec<usize>,
+}
+
+impl Workload {
+    /// The default workload of expression index range scans, primary key lookups, random row updates, inserts and
+    /// deletes on the [`Schema::Rowid`] table. Read-only threads run the reader scans and lookups of `counts`.
+    pub fn builtin(counts: BuiltinCounts) -> Self {
+        let BuiltinCounts {
+            scans,
+            lookups,
+            updates,
+            inserts,
+            deletes,
+            reader_scans,
+            reader_lookups,
+        } = counts;
+        Self {
+            name: String::new(),
+            schema: String::new(),
+            next_key: None,
+            size: Some("SELECT count(*) FROM tbl;".to_string()),
+            operations: vec![
+                Operation {
+                    name: "scan".to_string(),
+                    sql: "SELECT * FROM tbl WHERE substr(c, 1, 16)>=? ORDER BY substr(c, 1, 16) LIMIT 10;".to_string(),
+                    params: vec![Param::KeyPrefix { length: 16 }],
+                    counts: scans,
+                    reader_counts: reader_scans,
+                    resizes: false,
+                },
+                Operation {
+                    name: "lookup".to_string(),
+                    sql: String::new(),
+                    params: vec![Param::Key],
+                    counts: lookups,
+                    reader_counts: reader_lookups,
+                    resizes: false,
+                },
+                Operation {
+                    name: "update".to_string(),
+                    sql: String::new(),
+                    params: vec![Param::Blob { length: 200 }, Param::Hex { length: 64 }, Param::Key],
+                    counts: updates,
+                    reader_counts: Vec::new(),
+                    resizes: false,
+                },
+                Operation {
+                    name: "insert".to_string(),
+                    sql: String::new(),
+                    params: vec![Param::NewKey, Param::Blob { length: 200 }, Param::Hex { length: 64 }],
+                    counts: inserts,
+                    reader_counts: Vec::new(),
+                    resizes: true,
+                },
+                Operation {
+                    name: "delete".to_string(),
+                    sql: String::new(),
+                    params: vec![Param::Key],
+                    counts: deletes,
+                    reader_counts: Vec::new(),
+                    resizes: true,
+                },
+            ],
+        }
+        .with_schema(Schema::Rowid)
+    }
+
+    /// The builtin workload adapted to the table layout of `schema`, named `builtin-<schema>` for any but
+    /// [`Schema::Rowid`].
+    pub fn with_schema(mut self, schema: Schema) -> Self {
+        self.name = match schema {
+            Schema::Rowid => "builtin".to_string(),
+            schema => format!("builtin-{}", schema.name()),
+        };
+        self.schema = schema.script();
+        self.next_key = Some(schema.next_key().to_string());
+        for operation in &mut self.operations {
+            match operation.name.as_str() {
+                "lookup" => operation.sql = format!("SELECT * FROM tbl WHERE {};", schema.matches(1)),
+                "update" => operation.sql = format!("UPDATE tbl SET b=?1, c=?2 WHERE {};", schema.matches(3)),
+                "insert" => operation.sql = format!("INSERT INTO tbl VALUES ({}, ?2, ?3);", schema.key(1)),
+                "delete" => operation.sql = format!("DELETE FROM tbl WHERE {};", schema.matches(1)),
+                _ => {}
+            }
+        }
+        self
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let workload: Workload = toml::from_str(&fs::read_to_string(path)?)?;
+        if workload.operations.is_empty() {
+            return Err(anyhow::anyhow!("workload {:?} has no operations", workload.name));
+        }
+        for operation in &workload.operations {
+            if operation.counts.is_empty() {
+                return Err(anyhow::anyhow!("operation {:?} has no counts, use [0] to leave it out", operation.name));
+            }
+            for param in &operation.params {
+                if let Param::Integer { min, max } = param {
+                    if min > max {
+                        return Err(anyhow::anyhow!(
+                            "operation {:?} has an integer parameter with min {min} above max {max}",
+                            operation.name
+                        ));
+                    }
+                }
+            }
+        }
+        if workload.mixes().is_empty() {
+            return Err(anyhow::anyhow!("workload {:?} has no counts above zero", workload.name));
+        }
+        Ok(workload)
+    }
+
+    /// Run the queries of the `sampled` parameters against `conn` to read the values they draw from.
+    pub fn load(&mut self, conn: &Connection) -> Result<()> {
+        for param in self.operations.iter_mut().flat_map(|operation| &mut operation.params) {
+            if let Param::Sampled { query, values } = param {
+                let rows = conn
+                    .prepare(query)?
+                    .query_map([], |row| row.get::<_, Value>(0))?
+                    .collect::<Result<Vec<_>, _>>()?;
+                if rows.is_empty() {
+                    return Err(anyhow::anyhow!("sampled parameter query {query:?} returned no rows"));
+                }
+                *values = Arc::new(rows);
+            }
+        }
+        Ok(())
+    }
+
+    /// Every combination of per-transaction operation counts.
+    pub fn mixes(&self) -> Vec<Mix> {
+        self.named(self.operations.iter().map(|operation| operation.counts.clone()))
+    }
+
+    /// Every combination of per-transaction operation counts on the reader threads.
+    pub fn reader_mixes(&self) -> Vec<Mix> {
+        self.named(self.operations.iter().map(|operation| match operation.reader_counts.is_empty() {
+            true => vec![0],
+            false => operation.reader_counts.clone(),
+        }))
+    }
+
+    /// Every combination of `counts`, given in the same order as `operations`, that runs any operation.
+    fn named(&self, counts: impl Iterator<Item = Vec<usize>>) -> Vec<Mix> {
+        counts
+            .multi_cartesian_product()
+            .filter(|mix| mix.iter().any(|count| *count != 0))
+            .map(|mix| self.operations.iter().map(|operation| operation.name.clone()).zip(mix).collect())
+            .collect()
+    }
+
+    /// The counts of `mix` in the same order as `operations`, with zero for the operations it leaves out.
+    pub fn counts(&self, mix: &Mix) -> Result<Vec<usize>> {
+        if let Some(name) = mix.keys().find(|name| !self.operations.iter().any(|operation| &operation.name == *name)) {
+            return Err(anyhow::anyhow!("workload {:?} has no operation {name:?}", self.name));
+        }
+        Ok(self
+            .operations
+            .iter()
+            .map(|operation| mix.get(&operation.name).copied().unwrap_or(0))
+            .collect())
+    }
+}